@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::command::Command;
+use crate::error::{Error, Result};
+use crate::profile::profile_base::Profile;
+use crate::transport::usb::{GetHeader, SetHeader};
+
+/// Link types of USB captures
+struct LinkType;
+impl LinkType {
+    /// usbmon, 48 byte header
+    const USB_LINUX: u32 = 189;
+    /// usbmon via memory mapped interface, 64 byte header
+    const USB_LINUX_MMAPPED: u32 = 220;
+    /// USBPcap on Windows
+    const USBPCAP: u32 = 249;
+}
+
+/// Direction of a captured profile transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureDirection {
+    /// profile read from the device with GET_REPORT
+    Read,
+    /// profile written to the device with SET_REPORT (0xa0 0x11)
+    Write,
+}
+
+/// 1041 byte profile image reconstructed from a capture
+///
+/// * `timestamp`: capture time of the transfer
+/// * `direction`: whether the profile was read or written
+/// * `image`: the raw profile image
+#[derive(Debug, Clone)]
+pub struct CapturedProfile {
+    pub timestamp: Duration,
+    pub direction: CaptureDirection,
+    pub image: [u8; Profile::PROFILE_SIZE],
+}
+impl CapturedProfile {
+    /// returns the image as `Profile` to decode it with `MouseProfile`
+    pub fn profile(&self) -> Profile {
+        let mut profile = Profile::init();
+        profile.update(&mut self.image.clone());
+        profile
+    }
+}
+
+/// Completed control transfer found in a capture
+///
+/// * `setup`: setup packet
+/// * `data`: payload for OUT transfers, response for IN transfers
+struct ControlTransfer {
+    timestamp: Duration,
+    setup: [u8; 8],
+    data: Vec<u8>,
+}
+impl ControlTransfer {
+    fn request_type(&self) -> u8 {
+        self.setup[0]
+    }
+    fn request(&self) -> u8 {
+        self.setup[1]
+    }
+    fn value(&self) -> u16 {
+        u16::from_le_bytes([self.setup[2], self.setup[3]])
+    }
+
+    /// whether this is a feature report of report ID 0xa1 (or 0xa0 for writes)
+    fn profile_image(&self) -> Option<CapturedProfile> {
+        let image: [u8; Profile::PROFILE_SIZE] = self.data.as_slice().try_into().ok()?;
+        let feature = self.value() >> 8 == SetHeader::WVALUE >> 8;
+        let is_set = self.request_type() == SetHeader::BMREQUESTTYPE
+            && self.request() == SetHeader::BREQUEST
+            && feature
+            && Command::from_request(&image) == Some(Command::WriteProfile);
+        let is_get = self.request_type() == GetHeader::BMREQUESTTYPE
+            && self.request() == GetHeader::BREQUEST
+            && self.value() == GetHeader::WVALUE;
+        let direction = match (is_set, is_get) {
+            (true, _) => CaptureDirection::Write,
+            (_, true) => CaptureDirection::Read,
+            _ => return None,
+        };
+        Some(CapturedProfile {
+            timestamp: self.timestamp,
+            direction,
+            image,
+        })
+    }
+}
+
+/// Packet of a capture file
+///
+/// * `link_type`: link type of the interface the packet was captured on
+/// * `big_endian`: byte order of the capturing host
+struct Packet<'a> {
+    link_type: u32,
+    big_endian: bool,
+    timestamp: Duration,
+    data: &'a [u8],
+}
+
+/// Parses a pcap or pcapng capture of usbmon (Linux) or USBPcap (Windows) and
+/// returns all 1041 byte profile images transferred with feature reports of
+/// report ID 0xa1, in capture order.
+/// Only profiles sent as a single 1041 byte transfer are found, profiles
+/// split into several reports (e.g. 64 byte chunks) are not reassembled.
+pub fn import(path: &Path) -> Result<Vec<CapturedProfile>> {
+    let data = fs::read(path).map_err(|e| Error::file(path, e))?;
+    let packets = match data.get(..4) {
+        Some([0x0a, 0x0d, 0x0d, 0x0a]) => parse_pcapng(&data)?,
+        Some(_) => parse_pcap(&data)?,
+        None => return Err(capture_error("file too short")),
+    };
+
+    let mut usbmon = UsbmonDecoder::default();
+    let mut usbpcap = UsbpcapDecoder::default();
+    let mut transfers: Vec<ControlTransfer> = Vec::new();
+    for packet in packets {
+        let transfer = match packet.link_type {
+            LinkType::USB_LINUX => usbmon.decode(&packet, 48),
+            LinkType::USB_LINUX_MMAPPED => usbmon.decode(&packet, 64),
+            LinkType::USBPCAP => usbpcap.decode(&packet),
+            _ => None,
+        };
+        transfers.extend(transfer);
+    }
+    Ok(transfers
+        .iter()
+        .filter_map(ControlTransfer::profile_image)
+        .collect())
+}
+
+fn capture_error(msg: &str) -> Error {
+    Error::InvalidCapture(msg.into())
+}
+
+/// reads an unsigned integer of `N` bytes at `offset`
+fn uint<const N: usize>(data: &[u8], offset: usize, big_endian: bool) -> Option<u64> {
+    let bytes: [u8; N] = data.get(offset..offset + N)?.try_into().ok()?;
+    let mut value: u64 = 0;
+    for i in 0..N {
+        let byte = if big_endian {
+            bytes[i]
+        } else {
+            bytes[N - 1 - i]
+        };
+        value = (value << 8) | byte as u64;
+    }
+    Some(value)
+}
+
+/// parses a classic pcap file
+fn parse_pcap(data: &[u8]) -> Result<Vec<Packet<'_>>> {
+    let (big_endian, nanos) = match data.get(..4) {
+        Some([0xd4, 0xc3, 0xb2, 0xa1]) => (false, false),
+        Some([0xa1, 0xb2, 0xc3, 0xd4]) => (true, false),
+        Some([0x4d, 0x3c, 0xb2, 0xa1]) => (false, true),
+        Some([0xa1, 0xb2, 0x3c, 0x4d]) => (true, true),
+        _ => return Err(capture_error("not a pcap or pcapng file")),
+    };
+    let link_type = uint::<4>(data, 20, big_endian)
+        .ok_or_else(|| capture_error("truncated pcap header"))? as u32;
+
+    let mut packets = Vec::new();
+    let mut offset = 24;
+    while offset < data.len() {
+        let header = |field: usize| uint::<4>(data, offset + field, big_endian);
+        let (Some(secs), Some(frac), Some(len)) = (header(0), header(4), header(8)) else {
+            return Err(capture_error("truncated pcap record"));
+        };
+        let start = offset + 16;
+        let end = start + len as usize;
+        let packet = data
+            .get(start..end)
+            .ok_or_else(|| capture_error("truncated pcap record"))?;
+        let frac = if nanos {
+            Duration::from_nanos(frac)
+        } else {
+            Duration::from_micros(frac)
+        };
+        packets.push(Packet {
+            link_type,
+            big_endian,
+            timestamp: Duration::from_secs(secs) + frac,
+            data: packet,
+        });
+        offset = end;
+    }
+    Ok(packets)
+}
+
+/// parses a pcapng file, all sections and interfaces
+fn parse_pcapng(data: &[u8]) -> Result<Vec<Packet<'_>>> {
+    const SECTION_HEADER: u64 = 0x0a0d0d0a;
+    const INTERFACE_DESCRIPTION: u64 = 0x01;
+    const SIMPLE_PACKET: u64 = 0x03;
+    const ENHANCED_PACKET: u64 = 0x06;
+
+    let mut packets = Vec::new();
+    let mut big_endian = false;
+    // link type and timestamp resolution (units per second) per interface
+    let mut interfaces: Vec<(u32, u64)> = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        if uint::<4>(data, offset, false) == Some(SECTION_HEADER) {
+            big_endian = match data.get(offset + 8..offset + 12) {
+                Some([0x4d, 0x3c, 0x2b, 0x1a]) => false,
+                Some([0x1a, 0x2b, 0x3c, 0x4d]) => true,
+                _ => return Err(capture_error("invalid pcapng byte order magic")),
+            };
+            interfaces.clear();
+        }
+        let (Some(block_type), Some(block_len)) = (
+            uint::<4>(data, offset, big_endian),
+            uint::<4>(data, offset + 4, big_endian),
+        ) else {
+            return Err(capture_error("truncated pcapng block"));
+        };
+        let block_len = block_len as usize;
+        let block = data
+            .get(offset..offset + block_len)
+            .filter(|_| block_len >= 12)
+            .ok_or_else(|| capture_error("truncated pcapng block"))?;
+        let body = &block[8..block_len - 4];
+
+        match block_type {
+            INTERFACE_DESCRIPTION => {
+                let link_type = uint::<2>(body, 0, big_endian).unwrap_or(0) as u32;
+                interfaces.push((link_type, interface_resolution(body, big_endian)?));
+            }
+            ENHANCED_PACKET => {
+                let field = |offset: usize| uint::<4>(body, offset, big_endian);
+                let (Some(interface), Some(high), Some(low), Some(len)) =
+                    (field(0), field(4), field(8), field(12))
+                else {
+                    return Err(capture_error("truncated enhanced packet block"));
+                };
+                let &(link_type, resolution) = interfaces
+                    .get(interface as usize)
+                    .ok_or_else(|| capture_error("packet of unknown interface"))?;
+                let ticks = (high << 32) | low;
+                let packet = body
+                    .get(20..20 + len as usize)
+                    .ok_or_else(|| capture_error("truncated enhanced packet block"))?;
+                packets.push(Packet {
+                    link_type,
+                    big_endian,
+                    timestamp: Duration::from_secs(ticks / resolution)
+                        + Duration::from_nanos(
+                            // u128 as the resolution may be up to 2^63 units per second
+                            ((ticks % resolution) as u128 * 1_000_000_000 / resolution as u128)
+                                as u64,
+                        ),
+                    data: packet,
+                });
+            }
+            SIMPLE_PACKET => {
+                let &(link_type, _) = interfaces
+                    .first()
+                    .ok_or_else(|| capture_error("packet of unknown interface"))?;
+                let len = uint::<4>(body, 0, big_endian).unwrap_or(0) as usize;
+                let packet = body
+                    .get(4..(4 + len).min(body.len()))
+                    .ok_or_else(|| capture_error("truncated simple packet block"))?;
+                packets.push(Packet {
+                    link_type,
+                    big_endian,
+                    timestamp: Duration::ZERO,
+                    data: packet,
+                });
+            }
+            _ => {}
+        }
+        offset += block_len;
+    }
+    Ok(packets)
+}
+
+/// timestamp resolution in units per second given by the `if_tsresol`
+/// option of an interface description block, microseconds by default.
+/// Resolutions not fitting into u64 (above 10^19 or 2^63) are rejected.
+fn interface_resolution(body: &[u8], big_endian: bool) -> Result<u64> {
+    const IF_TSRESOL: u64 = 9;
+    let mut offset = 8;
+    while let (Some(code), Some(len)) = (
+        uint::<2>(body, offset, big_endian),
+        uint::<2>(body, offset + 2, big_endian),
+    ) {
+        if code == 0 {
+            break;
+        }
+        if code == IF_TSRESOL
+            && let Some(&resolution) = body.get(offset + 4)
+        {
+            let exponent = (resolution & 0x7f) as u32;
+            let base: u64 = if resolution & 0x80 == 0 { 10 } else { 2 };
+            return base.checked_pow(exponent).ok_or_else(|| {
+                capture_error(&format!(
+                    "timestamp resolution {base}^-{exponent} is not supported"
+                ))
+            });
+        }
+        offset += 4 + (len as usize).div_ceil(4) * 4;
+    }
+    Ok(1_000_000)
+}
+
+/// Pairs usbmon submissions and completions of control transfers
+///
+/// * `pending`: submitted transfers by URB id with setup packet and OUT data
+#[derive(Default)]
+struct UsbmonDecoder {
+    pending: HashMap<u64, ([u8; 8], Vec<u8>)>,
+}
+impl UsbmonDecoder {
+    const CONTROL: u64 = 2;
+
+    fn decode(&mut self, packet: &Packet, header_len: usize) -> Option<ControlTransfer> {
+        let data = packet.data;
+        let be = packet.big_endian;
+        let id = uint::<8>(data, 0, be)?;
+        let event = *data.get(8)?;
+        if uint::<1>(data, 9, be)? != Self::CONTROL {
+            return None;
+        }
+        let payload = data.get(header_len..).unwrap_or_default().to_vec();
+        match event {
+            b'S' => {
+                // flag_setup is 0 if the setup packet is present
+                if *data.get(14)? != 0 {
+                    return None;
+                }
+                let setup: [u8; 8] = data.get(40..48)?.try_into().ok()?;
+                self.pending.insert(id, (setup, payload));
+                None
+            }
+            b'C' => {
+                let (setup, out_data) = self.pending.remove(&id)?;
+                let status = uint::<4>(data, 28, be)?;
+                if status != 0 {
+                    return None;
+                }
+                let device_to_host = setup[0] & 0x80 != 0;
+                Some(ControlTransfer {
+                    timestamp: packet.timestamp,
+                    setup,
+                    data: if device_to_host { payload } else { out_data },
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Pairs USBPcap setup and completion packets of control transfers
+///
+/// * `pending`: submitted transfers by IRP id with setup packet and OUT data
+#[derive(Default)]
+struct UsbpcapDecoder {
+    pending: HashMap<u64, ([u8; 8], Vec<u8>)>,
+}
+impl UsbpcapDecoder {
+    const CONTROL: u8 = 2;
+    const STAGE_SETUP: u8 = 0;
+    const STAGE_DATA: u8 = 1;
+
+    fn decode(&mut self, packet: &Packet) -> Option<ControlTransfer> {
+        let data = packet.data;
+        let header_len = uint::<2>(data, 0, false)? as usize;
+        let irp = uint::<8>(data, 2, false)?;
+        let status = uint::<4>(data, 10, false)?;
+        let from_device = data.get(16)? & 0x01 != 0;
+        if *data.get(22)? != Self::CONTROL || header_len < 28 {
+            return None;
+        }
+        let stage = *data.get(27)?;
+        let payload = data.get(header_len..).unwrap_or_default();
+
+        if !from_device {
+            match stage {
+                Self::STAGE_SETUP => {
+                    let setup: [u8; 8] = payload.get(..8)?.try_into().ok()?;
+                    self.pending.insert(irp, (setup, payload[8..].to_vec()));
+                }
+                Self::STAGE_DATA => {
+                    self.pending.get_mut(&irp)?.1.extend_from_slice(payload);
+                }
+                _ => {}
+            }
+            return None;
+        }
+
+        let (setup, out_data) = self.pending.remove(&irp)?;
+        if status != 0 {
+            return None;
+        }
+        let device_to_host = setup[0] & 0x80 != 0;
+        Some(ControlTransfer {
+            timestamp: packet.timestamp,
+            setup,
+            data: if device_to_host {
+                payload.to_vec()
+            } else {
+                out_data
+            },
+        })
+    }
+}
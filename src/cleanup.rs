@@ -0,0 +1,128 @@
+use std::panic;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Once};
+use std::{mem, ptr, thread};
+
+use rusb::{Context, DeviceHandle};
+
+use crate::error::{Error, Result};
+
+/// Interface claimed by a `UsbTransport`
+///
+/// * `id`: id returned by `register`
+/// * `handle`: handle shared with the transport
+/// * `interface`: claimed interface
+/// * `detached`: whether the kernel driver has to be reattached
+struct Claim {
+    id: u64,
+    handle: Arc<DeviceHandle<Context>>,
+    interface: u8,
+    detached: bool,
+}
+
+/// all interfaces currently claimed by this process
+static CLAIMS: Mutex<Vec<Claim>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static INSTALL: Once = Once::new();
+
+/// locks the claims, a panic while holding the lock must not prevent the cleanup
+fn claims() -> MutexGuard<'static, Vec<Claim>> {
+    CLAIMS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// registers a claimed `interface` for cleanup and returns the id to `release` it
+pub(crate) fn register(handle: Arc<DeviceHandle<Context>>, interface: u8, detached: bool) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    claims().push(Claim {
+        id,
+        handle,
+        interface,
+        detached,
+    });
+    id
+}
+
+/// releases the interface registered as `id`, does nothing if it was already released
+pub(crate) fn release(id: u64) {
+    let claim = {
+        let mut claims = claims();
+        let Some(position) = claims.iter().position(|claim| claim.id == id) else {
+            return;
+        };
+        claims.remove(position)
+    };
+    release_claim(&claim);
+}
+
+/// releases all claimed interfaces and reattaches their kernel drivers
+pub fn release_all() {
+    let claimed = mem::take(&mut *claims());
+    for claim in &claimed {
+        release_claim(claim);
+    }
+}
+
+/// releases `claim`, errors are reported on stderr, a device which is already gone is ignored
+fn release_claim(claim: &Claim) {
+    // releasing interface
+    match claim.handle.release_interface(claim.interface) {
+        Ok(()) | Err(rusb::Error::NoDevice) => {}
+        Err(e) => eprintln!("failed to release interface: {e}"),
+    }
+    // reattaching kernel
+    if !claim.detached {
+        return;
+    }
+    match claim.handle.attach_kernel_driver(claim.interface) {
+        Ok(()) | Err(rusb::Error::NoDevice) => {}
+        Err(e) => eprintln!("failed to attach to kernel driver when closing: {e}"),
+    }
+}
+
+/// Installs a panic hook and SIGINT/SIGTERM/SIGHUP handling which release all
+/// claimed interfaces and reattach the kernel drivers before the process ends.
+///
+/// The signals are blocked for the calling thread and handled on a dedicated
+/// thread, so this has to be called at the start of `main` before any other
+/// thread is spawned. Calling it more than once has no effect.
+pub fn install() -> Result<()> {
+    let mut res: Result<()> = Ok(());
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            release_all();
+            previous(info);
+        }));
+        res = install_signal_thread();
+    });
+    res
+}
+
+/// blocks the termination signals and waits for them on a separate thread
+fn install_signal_thread() -> Result<()> {
+    // SAFETY: the signal set is initialized by sigemptyset before use
+    let set = unsafe {
+        let mut set: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            libc::sigaddset(&mut set, signal);
+        }
+        let res = libc::pthread_sigmask(libc::SIG_BLOCK, &set, ptr::null_mut());
+        if res != 0 {
+            return Err(Error::Io(std::io::Error::from_raw_os_error(res)));
+        }
+        set
+    };
+
+    thread::Builder::new()
+        .name("signal-cleanup".into())
+        .spawn(move || {
+            let mut signal: libc::c_int = 0;
+            // SAFETY: `set` is a valid signal set and `signal` a valid output
+            while unsafe { libc::sigwait(&set, &mut signal) } != 0 {}
+            release_all();
+            std::process::exit(128 + signal);
+        })
+        .map_err(Error::Io)?;
+    Ok(())
+}
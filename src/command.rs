@@ -0,0 +1,218 @@
+use crate::device_info::DeviceInfo;
+use crate::error::{Error, Result};
+use crate::profile::profile_base::Profile;
+
+/// length of the handshake and request payloads
+const PAYLOAD_LENGTH: usize = 64;
+
+/// Commands of the 0xa0/0xa1 feature report family
+/// every command is sent as SET_REPORT starting with its two byte signature,
+/// commands with a response are followed by a GET_REPORT:
+/// - Handshake: 0xa1 0x02, answered with 64 bytes decoded as `DeviceInfo`
+/// - ReadProfile: 0xa1 0x12, answered with the 1041 byte profile
+/// - WriteProfile: 0xa0 0x11 followed by the profile, no response
+///
+/// Newly discovered opcodes are added as variant here, the tables of the
+/// functions below describe the complete layout of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Handshake,
+    ReadProfile,
+    WriteProfile,
+}
+impl Command {
+    /// all known commands
+    pub const ALL: [Command; 3] = [Self::Handshake, Self::ReadProfile, Self::WriteProfile];
+
+    /// report ID and opcode starting the request
+    pub const fn signature(self) -> [u8; 2] {
+        match self {
+            Self::Handshake => [0xa1, 0x02],
+            Self::ReadProfile => [0xa1, 0x12],
+            Self::WriteProfile => [0xa0, 0x11],
+        }
+    }
+
+    /// total length of the SET_REPORT request including the signature
+    pub const fn request_length(self) -> usize {
+        match self {
+            Self::Handshake | Self::ReadProfile => PAYLOAD_LENGTH,
+            Self::WriteProfile => Profile::PROFILE_SIZE,
+        }
+    }
+
+    /// length of the GET_REPORT response, `None` if the command has no response
+    pub const fn response_length(self) -> Option<usize> {
+        match self {
+            Self::Handshake => Some(PAYLOAD_LENGTH),
+            Self::ReadProfile => Some(Profile::PROFILE_SIZE),
+            Self::WriteProfile => None,
+        }
+    }
+
+    /// Bytes every valid response starts with. The header of the profile
+    /// response is not documented, so it is not checked.
+    pub const fn response_header(self) -> &'static [u8] {
+        match self {
+            Self::Handshake => &[0xa1],
+            Self::ReadProfile | Self::WriteProfile => &[],
+        }
+    }
+
+    /// returns the command whose signature `request` starts with
+    pub fn from_request(request: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|command| request.starts_with(&command.signature()))
+    }
+
+    /// Builds the request: the signature followed by `payload`, zero padded
+    /// to `request_length`.
+    ///
+    /// * `payload`: command data following the signature
+    pub fn request(self, payload: &[u8]) -> Result<Vec<u8>> {
+        let mut request = vec![0; self.request_length()];
+        let data = &mut request[2..];
+        if payload.len() > data.len() {
+            return Err(Error::Protocol(format!(
+                "{self:?} payload has {} bytes, at most {} fit",
+                payload.len(),
+                data.len()
+            )));
+        }
+        data[..payload.len()].copy_from_slice(payload);
+        request[..2].copy_from_slice(&self.signature());
+        Ok(request)
+    }
+
+    /// Validates length and header of `response` and decodes it.
+    /// A mismatching handshake fails with `Error::HandshakeMismatch`.
+    pub fn parse_response(self, response: &[u8]) -> Result<Response> {
+        let Some(expected) = self.response_length() else {
+            return Err(Error::Protocol(format!("{self:?} has no response")));
+        };
+        if response.len() != expected {
+            return Err(Error::ShortRead {
+                expected,
+                actual: response.len(),
+            });
+        }
+        match self {
+            // the handshake validates its header itself
+            Self::Handshake => Ok(Response::DeviceInfo(DeviceInfo::parse(response)?)),
+            Self::ReadProfile => {
+                if !response.starts_with(self.response_header()) {
+                    return Err(Error::Protocol(format!(
+                        "unexpected {self:?} response header {:02X?}",
+                        &response[..self.response_header().len()]
+                    )));
+                }
+                let mut profile = Box::new([0; Profile::PROFILE_SIZE]);
+                profile.copy_from_slice(response);
+                Ok(Response::Profile(profile))
+            }
+            Self::WriteProfile => unreachable!("commands without response return above"),
+        }
+    }
+}
+
+/// Decoded response of a `Command`
+/// - DeviceInfo: handshake response
+/// - Profile: profile image including its first two bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    DeviceInfo(DeviceInfo),
+    Profile(Box<[u8; Profile::PROFILE_SIZE]>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_info::FirmwareVersion;
+    use crate::transport::simulated::SimulatedDevice;
+
+    /// (command, signature, request length, response length)
+    const COMMANDS: [(Command, [u8; 2], usize, Option<usize>); 3] = [
+        (Command::Handshake, [0xa1, 0x02], 64, Some(64)),
+        (Command::ReadProfile, [0xa1, 0x12], 64, Some(1041)),
+        (Command::WriteProfile, [0xa0, 0x11], 1041, None),
+    ];
+
+    #[test]
+    fn requests_start_with_signature_and_are_zero_padded() {
+        for (command, signature, request_length, response_length) in COMMANDS {
+            assert_eq!(command.signature(), signature);
+            assert_eq!(command.response_length(), response_length);
+
+            let request = command.request(&[1, 2, 3]).unwrap();
+            assert_eq!(request.len(), request_length, "{command:?}");
+            assert_eq!(request[..5], [signature[0], signature[1], 1, 2, 3]);
+            assert!(request[5..].iter().all(|&byte| byte == 0), "{command:?}");
+            assert_eq!(Command::from_request(&request), Some(command));
+
+            let full = vec![0xff; request_length - 2];
+            assert_eq!(command.request(&full).unwrap()[2..], full[..]);
+            assert!(matches!(
+                command.request(&vec![0; request_length - 1]),
+                Err(Error::Protocol(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn from_request_needs_a_known_signature() {
+        for request in [
+            &[][..],
+            &[0xa1],
+            &[0xa1, 0x13],
+            &[0xa0, 0x12],
+            &[0x02, 0xa1],
+        ] {
+            assert_eq!(Command::from_request(request), None, "{request:02x?}");
+        }
+    }
+
+    #[test]
+    fn responses_of_wrong_length_are_rejected() {
+        for (command, _, _, response_length) in COMMANDS {
+            let Some(expected) = response_length else {
+                assert!(matches!(
+                    command.parse_response(&[0xa1]),
+                    Err(Error::Protocol(_))
+                ));
+                continue;
+            };
+            for actual in [0, expected - 1, expected + 1] {
+                assert!(
+                    matches!(
+                        command.parse_response(&vec![0xa1; actual]),
+                        Err(Error::ShortRead { expected: e, actual: a }) if e == expected && a == actual
+                    ),
+                    "{command:?} accepted {actual} bytes"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn responses_are_decoded() {
+        let handshake = SimulatedDevice::HANDSHAKE_RESPONSE;
+        let Ok(Response::DeviceInfo(info)) = Command::Handshake.parse_response(&handshake) else {
+            panic!("handshake response not decoded");
+        };
+        assert_eq!(info.firmware, FirmwareVersion::new(2, 1, 0));
+
+        let mut wrong_header = handshake;
+        wrong_header[0] = 0xa0;
+        assert!(matches!(
+            Command::Handshake.parse_response(&wrong_header),
+            Err(Error::HandshakeMismatch { .. })
+        ));
+
+        let profile = SimulatedDevice::default_profile();
+        assert_eq!(
+            Command::ReadProfile.parse_response(&profile).unwrap(),
+            Response::Profile(Box::new(profile))
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::transport::usb::UsbTransport;
+
+/// Firmware version as reported at offsets 17-19 of the handshake response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+impl FirmwareVersion {
+    pub const fn new(major: u8, minor: u8, patch: u8) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Decoded handshake response of the device
+/// layout of the 64 byte response:
+/// - 0: report ID 0xa1
+/// - 1: protocol version
+/// - 17, 18, 19: firmware version (major, minor, patch)
+/// - 20, 21: VID (little endian)
+/// - 22, 23: PID (little endian)
+///
+/// * `protocol_version`: version of the configuration protocol
+/// * `firmware`: firmware version of the device
+/// * `vid`: vendor ID reported by the device
+/// * `pid`: product ID reported by the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub protocol_version: u8,
+    pub firmware: FirmwareVersion,
+    pub vid: u16,
+    pub pid: u16,
+}
+impl DeviceInfo {
+    const REPORT_ID: u8 = 0xa1;
+    const LENGTH: usize = 24;
+
+    /// Decodes the handshake `response` and validates that it belongs to an
+    /// XM2 8k v2, any firmware version is accepted.
+    pub fn parse(response: &[u8]) -> Result<Self> {
+        let mismatch = || Error::HandshakeMismatch {
+            response: response.to_vec(),
+        };
+        if response.len() < Self::LENGTH || response[0] != Self::REPORT_ID {
+            return Err(mismatch());
+        }
+        let info = Self {
+            protocol_version: response[1],
+            firmware: FirmwareVersion::new(response[17], response[18], response[19]),
+            vid: u16::from_le_bytes([response[20], response[21]]),
+            pid: u16::from_le_bytes([response[22], response[23]]),
+        };
+        if info.vid != UsbTransport::VID || info.pid != UsbTransport::PID {
+            return Err(mismatch());
+        }
+        Ok(info)
+    }
+}
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "VID {:04x} PID {:04x} firmware {} protocol {}",
+            self.vid, self.pid, self.firmware, self.protocol_version
+        )
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::device_info::FirmwareVersion;
+use crate::profile::profile_attribute_args::FirmwareRange;
+
+/// Errors while talking to the mouse
+/// the variants allow callers to give actionable messages, see `hint`
+#[derive(Debug)]
+pub enum Error {
+    /// no XM2 8k v2 is connected (or it was unplugged)
+    DeviceNotFound,
+    /// the device node is not accessible for the current user
+    PermissionDenied,
+    /// the interface is claimed by a kernel driver or another process
+    KernelDriverBusy,
+    /// another instance of the tool holds the device lock
+    DeviceBusy { pid: Option<u32> },
+    /// a transfer did not complete in time
+    Timeout,
+    /// the handshake response was not the one of an XM2 8k v2
+    HandshakeMismatch { response: Vec<u8> },
+    /// the device returned less bytes than requested
+    ShortRead { expected: usize, actual: usize },
+    /// the device accepted less bytes than sent
+    ShortWrite { expected: usize, actual: usize },
+    /// the device (or a test double) answered a request it did not expect
+    Protocol(String),
+    /// a profile image (e.g. loaded from a file) is malformed
+    InvalidProfile(String),
+    /// a value given for a profile field could not be parsed or is out of range
+    InvalidValue { field: String, message: String },
+    /// a profile field does not exist in the profile layout of the firmware
+    UnsupportedField {
+        field: String,
+        firmware: FirmwareVersion,
+        supported: FirmwareRange,
+    },
+    /// a pcap/pcapng capture file is malformed
+    InvalidCapture(String),
+    /// any other libusb error
+    Usb(rusb::Error),
+    /// reading or writing a file (profile image, capture, recording, log) failed
+    File { path: PathBuf, source: io::Error },
+    /// any other I/O error
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// error accessing the file at `path`
+    pub fn file(path: &Path, source: io::Error) -> Self {
+        Self::File {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// returns a hint on how the user can resolve the error
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::DeviceNotFound => Some("check that the mouse is plugged in"),
+            Self::PermissionDenied => Some(
+                "install the udev rule with `sudo cp 99-egg_xm28kv2.rules /etc/udev/rules.d/` and replug the mouse",
+            ),
+            Self::KernelDriverBusy => {
+                Some("close other programs configuring the mouse and try again")
+            }
+            Self::DeviceBusy { .. } => {
+                Some("wait for the other instance to finish or use --wait-lock")
+            }
+            Self::Timeout => Some("replug the mouse and try again"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeviceNotFound => write!(f, "device not found"),
+            Self::PermissionDenied => write!(f, "permission denied accessing the device"),
+            Self::KernelDriverBusy => write!(f, "device interface is busy"),
+            Self::DeviceBusy { pid: Some(pid) } => write!(f, "device busy, held by PID {pid}"),
+            Self::DeviceBusy { pid: None } => write!(f, "device busy, held by another process"),
+            Self::Timeout => write!(f, "transfer timed out"),
+            Self::HandshakeMismatch { response } => {
+                write!(f, "handshake response did not match: {response:02X?}")
+            }
+            Self::ShortRead { expected, actual } => {
+                write!(f, "short read: expected {expected} bytes, got {actual}")
+            }
+            Self::ShortWrite { expected, actual } => {
+                write!(f, "short write: expected {expected} bytes, sent {actual}")
+            }
+            Self::Protocol(msg) => write!(f, "protocol error: {msg}"),
+            Self::InvalidProfile(msg) => write!(f, "invalid profile: {msg}"),
+            Self::InvalidValue { field, message } => write!(f, "{field}: {message}"),
+            Self::UnsupportedField {
+                field,
+                firmware,
+                supported,
+            } => write!(
+                f,
+                "{field} is not supported by firmware {firmware}, only by {supported}"
+            ),
+            Self::InvalidCapture(msg) => write!(f, "invalid capture: {msg}"),
+            Self::Usb(e) => write!(f, "usb error: {e}"),
+            Self::File { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Io(e) => write!(f, "i/o error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Usb(e) => Some(e),
+            Self::File { source, .. } => Some(source),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusb::Error> for Error {
+    fn from(e: rusb::Error) -> Self {
+        match e {
+            rusb::Error::NotFound | rusb::Error::NoDevice => Self::DeviceNotFound,
+            rusb::Error::Access => Self::PermissionDenied,
+            rusb::Error::Busy => Self::KernelDriverBusy,
+            rusb::Error::Timeout => Self::Timeout,
+            e => Self::Usb(e),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::profile::profile_base::Profile;
+
+/// Byte which changed between two profile snapshots
+///
+/// * `address`: position in the profile buffer
+/// * `before`: value in the first snapshot
+/// * `after`: value in the second snapshot
+/// * `claimed_by`: name of the `ProfileAttribute` mapping the address, `None` if unknown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedByte {
+    pub address: usize,
+    pub before: u8,
+    pub after: u8,
+    pub claimed_by: Option<String>,
+}
+
+/// Result of one exploration step, the user changed a single option
+/// (described by `label`) between two snapshots
+///
+/// * `timestamp`: time of the second snapshot since the unix epoch
+/// * `label`: description of the changed option
+/// * `changes`: all bytes which changed, sorted by address
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub timestamp: Duration,
+    pub label: String,
+    pub changes: Vec<ChangedByte>,
+}
+impl Finding {
+    /// compares the snapshots `before` and `after`
+    pub fn new(label: &str, before: &Profile, after: &Profile) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            label: label.into(),
+            changes: diff(before, after),
+        }
+    }
+
+    /// Appends the finding as a single JSON line to the log at `path`:
+    /// `{"timestamp":..,"label":..,"changes":[{"address":..,"before":..,"after":..,"claimed_by":..}]}`
+    pub fn append_to_log(&self, path: &Path) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::file(path, e))?;
+        writeln!(file, "{}", self.to_json()).map_err(|e| Error::file(path, e))
+    }
+
+    /// serializes the finding as single line JSON object
+    pub fn to_json(&self) -> String {
+        let changes: Vec<String> = self
+            .changes
+            .iter()
+            .map(|change| {
+                format!(
+                    "{{\"address\":{},\"before\":{},\"after\":{},\"claimed_by\":{}}}",
+                    change.address,
+                    change.before,
+                    change.after,
+                    change
+                        .claimed_by
+                        .as_deref()
+                        .map_or("null".into(), json_string)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"timestamp\":{},\"label\":{},\"changes\":[{}]}}",
+            self.timestamp.as_secs(),
+            json_string(&self.label),
+            changes.join(",")
+        )
+    }
+}
+
+/// Returns all bytes differing between `before` and `after`, annotated with
+/// the `ProfileAttribute` claiming the address. The read/write flags at [0]
+/// and [1] are skipped.
+pub fn diff(before: &Profile, after: &Profile) -> Vec<ChangedByte> {
+    let claimed = claimed_addresses(before);
+    (2..Profile::PROFILE_SIZE)
+        .filter(|&address| before.profile_buf[address] != after.profile_buf[address])
+        .map(|address| ChangedByte {
+            address,
+            before: before.profile_buf[address],
+            after: after.profile_buf[address],
+            claimed_by: claimed.get(&address).cloned(),
+        })
+        .collect()
+}
+
+/// maps every address (including data fields) claimed by a `ProfileAttribute` to its name
+pub fn claimed_addresses(profile: &Profile) -> HashMap<usize, String> {
+    let mut claimed = HashMap::new();
+    for attribute in profile.profile_fields.hashmap().values() {
+        for address in attribute.all_addresses() {
+            claimed.insert(address, attribute.name.clone());
+        }
+    }
+    claimed
+}
+
+/// quotes and escapes `s` as JSON string
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_string_escapes_quotes_and_control_characters() {
+        assert_eq!(json_string(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(json_string("a\\b"), r#""a\\b""#);
+        assert_eq!(json_string("\n\r\t"), r#""\n\r\t""#);
+        assert_eq!(json_string("\u{1}\u{1f}"), r#""\u0001\u001f""#);
+        // non-ASCII is valid in JSON strings and kept as UTF-8
+        assert_eq!(json_string("Größe ✓"), "\"Größe ✓\"");
+    }
+
+    #[test]
+    fn finding_is_serialized_as_single_line() {
+        let finding = Finding {
+            timestamp: Duration::from_secs(1_700_000_000),
+            label: "LOD \"2\"\nset".into(),
+            changes: vec![
+                ChangedByte {
+                    address: 2,
+                    before: 0x00,
+                    after: 0xff,
+                    claimed_by: None,
+                },
+                ChangedByte {
+                    address: 25,
+                    before: 0x00,
+                    after: 0x05,
+                    claimed_by: Some("LOD (Lift-Off Distance)".into()),
+                },
+            ],
+        };
+        assert_eq!(
+            finding.to_json(),
+            r#"{"timestamp":1700000000,"label":"LOD \"2\"\nset","changes":[{"address":2,"before":0,"after":255,"claimed_by":null},{"address":25,"before":0,"after":5,"claimed_by":"LOD (Lift-Off Distance)"}]}"#
+        );
+    }
+
+    #[test]
+    fn diff_reports_changed_addresses_with_their_attribute() {
+        let before = Profile::init();
+        let mut after = Profile::init();
+        for address in [0, 1, 2, 25, 55, 82, Profile::PROFILE_SIZE - 1] {
+            after.profile_buf[address] ^= 0xff;
+        }
+        let changes = diff(&before, &after);
+        let expected = [
+            (2, None),
+            (25, Some("LOD (Lift-Off Distance)")),
+            (55, Some("DPI Setting for Profile 1")),
+            (82, Some("Right Mouse Button")),
+            (Profile::PROFILE_SIZE - 1, None),
+        ];
+        assert_eq!(changes.len(), expected.len());
+        for (change, (address, claimed_by)) in changes.iter().zip(expected) {
+            assert_eq!(change.address, address);
+            assert_eq!(change.before, before.profile_buf[address]);
+            assert_eq!(change.after, !change.before);
+            assert_eq!(change.claimed_by.as_deref(), claimed_by);
+        }
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn claimed_addresses_include_data_fields() {
+        let claimed = claimed_addresses(&Profile::init());
+        for address in 78..=83 {
+            assert_eq!(claimed[&address], "Right Mouse Button");
+        }
+        for address in 52..=55 {
+            assert_eq!(claimed[&address], "DPI Setting for Profile 1");
+        }
+        assert!(!claimed.contains_key(&0));
+        assert!(!claimed.contains_key(&1));
+        assert!(!claimed.contains_key(&126));
+    }
+}
@@ -1,174 +1,428 @@
+use std::path::Path;
+use std::thread;
 use std::time::Duration;
 
-use crate::Profile;
-use rusb::{self, Context, DeviceHandle, UsbContext};
-/// Header for SET_REPORT requests:
-/// predefines all the headerfields for the usb interface call
-/// over the handler
-struct SetHeader;
-impl SetHeader {
-    const BMREQUESTTYPE: u8 = 0x21;
-    const BREQUEST: u8 = 0x09;
-    const WVALUE: u16 = 0x03a1;
-    const WINDEX: u16 = 0x0001;
-}
+use crate::command::{Command, Response};
+use crate::device_info::DeviceInfo;
+use crate::error::{Error, Result};
+use crate::profile::profile_base::Profile;
+use crate::transport::{
+    Transport,
+    usb::{DeviceEntry, DeviceSelector, UsbTransport},
+};
 
-/// Header for GET_REPORT requests:
-/// predefines all the headerfields for the usb interface vall
-/// over the handler
-struct GetHeader;
-impl GetHeader {
-    const BMREQUESTTYPE: u8 = 0xa1;
-    const BREQUEST: u8 = 0x01;
-    const WVALUE: u16 = 0x03a1;
-    const WINDEX: u16 = 0x0001;
+/// Policy applied to every request/response exchange of the `Handler`
+///
+/// * `timeout`: timeout of a single transfer
+/// * `retries`: number of retries after a failed exchange
+/// * `backoff`: pause before every retry
+/// * `rehandshake`: whether to repeat the handshake before retrying a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+    pub backoff: Duration,
+    pub rehandshake: bool,
+}
+impl Default for TransferPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(1),
+            retries: 2,
+            backoff: Duration::from_millis(100),
+            rehandshake: true,
+        }
+    }
 }
 
 /// Handler object for the connection to the mouse
 /// provides functions for handling the connection such as
-/// - init: establishing the connection and initializing handle
+/// - init: establishing a libusb connection and initializing handle
+/// - open: same as init for the device chosen by a `DeviceSelector`
+/// - list: listing all connected devices
+/// - recover: reattaching the kernel driver left detached by a crashed run
+/// - new: wrapping an arbitrary `Transport`
+/// - set_policy: overriding the default `TransferPolicy`
+/// - handshake: performing the handshake and returning the decoded `DeviceInfo`
 /// - read_profile: reading a profile from the device and return it
-/// - write_profile: write a profile to the device
-/// handles closing connections automatically on destruction of the
-/// object
+/// - write_profile: write a profile to the device and verify it by reading it back
+/// - factory_reset: back up the current profile and write the default values
 ///
-/// * `handle`: handle to the usb device
-pub struct Handler {
-    handle: DeviceHandle<Context>,
+/// closing the connection is left to the transport
+///
+/// * `transport`: transport exchanging the feature reports with the device
+/// * `policy`: timeout and retry policy of the exchanges
+pub struct Handler<T: Transport = UsbTransport> {
+    transport: T,
+    policy: TransferPolicy,
 }
-impl Handler {
-    const VID: u16 = 0x3367;
-    const PID: u16 = 0x1980;
-    const INTERFACE: u8 = 0x01;
-    const PAYLOAD_LENGTH: usize = 64;
-
-    pub fn init() -> Self {
-        //  creating USB device context
-        let context: Context = Context::new().expect("failed to create context");
-
-        // creating a device handle for usb device
-        let device_handle: DeviceHandle<Context> = context
-            .open_device_with_vid_pid(Self::VID, Self::PID)
-            .expect("failed to open device");
-
-        // if kernel driver is active detach it
-        if device_handle
-            .kernel_driver_active(Self::INTERFACE)
-            .expect("failed to determine if kernel driver is active")
-        {
-            device_handle
-                .detach_kernel_driver(Self::INTERFACE)
-                .expect("failed to detach kernel driver");
-        }
+impl Handler<UsbTransport> {
+    pub fn init() -> Result<Self> {
+        Ok(Self::new(UsbTransport::init()?))
+    }
 
-        // claim interface
-        if device_handle.claim_interface(Self::INTERFACE).is_err() {
-            device_handle
-                .detach_kernel_driver(Self::INTERFACE)
-                .expect("failed to detach kernel driver claiming device");
-            panic!("failed claiming device");
-        }
+    pub fn open(selector: &DeviceSelector) -> Result<Self> {
+        Ok(Self::new(UsbTransport::open(Some(selector), None)?))
+    }
+
+    pub fn list() -> Result<Vec<DeviceEntry>> {
+        UsbTransport::list()
+    }
+
+    pub fn recover(selector: Option<&DeviceSelector>) -> Result<bool> {
+        UsbTransport::recover(selector)
+    }
+}
+impl<T: Transport> Handler<T> {
+    pub fn new(transport: T) -> Self {
         Self {
-            handle: device_handle,
+            transport,
+            policy: TransferPolicy::default(),
         }
     }
-    pub fn read_profile(&self) -> Result<Profile, String> {
-        use GetHeader as GH;
-        use SetHeader as SH;
 
+    /// returns the transfer policy
+    pub fn policy(&self) -> &TransferPolicy {
+        &self.policy
+    }
+
+    /// replaces the transfer policy
+    pub fn set_policy(&mut self, policy: TransferPolicy) {
+        self.policy = policy;
+    }
+
+    /// returns the transport the handler talks through
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn read_profile(&self) -> Result<Profile> {
         // initializing empty profile
         let mut profile: Profile = Profile::init();
 
-        let mut payload_handshake: [u8; Self::PAYLOAD_LENGTH] = [0; Self::PAYLOAD_LENGTH];
-        let mut payload_get: [u8; Self::PAYLOAD_LENGTH] = [0; Self::PAYLOAD_LENGTH];
-        payload_handshake[0] = 0xa1;
-        payload_handshake[1] = 0x02;
-        let mut payload_read: [u8; Self::PAYLOAD_LENGTH] = [0; Self::PAYLOAD_LENGTH];
-        payload_read[0] = 0xa1;
-        payload_read[1] = 0x12;
-        let handshake_response: [u8; Self::PAYLOAD_LENGTH] = [
-            0xa1, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x67, 0x33, 0x80, 0x19, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-        ];
+        let info = self.handshake()?;
+        profile.firmware = Some(info.firmware);
+        self.read_profile_buf(&mut profile.profile_buf)?;
 
-        // Requesting Handshake
-        self.handle
-            .write_control(
-                SH::BMREQUESTTYPE,
-                SH::BREQUEST,
-                SH::WVALUE,
-                SH::WINDEX,
-                payload_handshake.as_mut_slice(),
-                Duration::new(1000, 0),
-            )
-            .map_err(|e| format!("handshake failed: {e}"))?;
-
-        // Reading Handshake
-        self.handle
-            .read_control(
-                GH::BMREQUESTTYPE,
-                GH::BREQUEST,
-                GH::WVALUE,
-                GH::WINDEX,
-                payload_get.as_mut_slice(),
-                Duration::new(1000, 0),
-            )
-            .map_err(|e| format!("reading handshake failed: {e}"))?;
-
-        // Validating Handshake
-        if !(payload_get == handshake_response) {
-            return Err("handshake response did not match".into());
-        }
-
-        // Requesting Read Profile
-        self.handle
-            .write_control(
-                SH::BMREQUESTTYPE,
-                SH::BREQUEST,
-                SH::WVALUE,
-                SH::WINDEX,
-                payload_read.as_mut_slice(),
-                Duration::new(1000, 0),
-            )
-            .map_err(|e| format!("read request failed: {e}"))?;
-
-        // reading profile to `profile`
-        self.handle
-            .read_control(
-                GetHeader::BMREQUESTTYPE,
-                GetHeader::BREQUEST,
-                GetHeader::WVALUE,
-                GetHeader::WINDEX,
-                profile.profile_buf.as_mut_slice(),
-                Duration::new(1000, 0),
-            )
-            .expect("failed reading profile");
         // returning profile
         Ok(profile)
     }
 
-    fn write_profile(&self, profile: &mut Profile) {
+    /// Writes `profile` to the device and reads it back afterwards.
+    /// Every address and data field mapped in `MouseProfile` is compared
+    /// between the written and the read back buffer, differing bytes are
+    /// listed in the returned `WriteReport`.
+    /// Fails with `Error::UnsupportedField` before writing if the profile
+    /// changes a field the connected firmware does not support.
+    ///
+    /// * `profile`: profile to write, [0] and [1] are overwritten with the write flags
+    pub fn write_profile(&self, profile: &mut Profile) -> Result<WriteReport> {
         // setting set_report signature to buf
-        profile.profile_buf[0] = 0xa0;
-        profile.profile_buf[1] = 0x11;
+        profile.profile_buf[..2].copy_from_slice(&Command::WriteProfile.signature());
+
+        let info = self.handshake()?;
+        self.check_unsupported_fields(profile, &info)?;
+
+        // Writing Profile
+        self.with_retries(true, || {
+            self.exchange(Command::WriteProfile, &profile.profile_buf[2..])
+        })?;
+
+        // Reading back Profile
+        let mut readback: Profile = Profile::init();
+        self.handshake()?;
+        self.read_profile_buf(&mut readback.profile_buf)?;
+
+        // Comparing all mapped addresses including data fields
+        let mut mismatches: Vec<ByteMismatch> = Vec::new();
+        for attribute in profile.profile_fields.hashmap().values() {
+            for address in attribute.all_addresses() {
+                if profile.profile_buf[address] != readback.profile_buf[address] {
+                    mismatches.push(ByteMismatch {
+                        address,
+                        field: attribute.name.clone(),
+                        written: profile.profile_buf[address],
+                        read: readback.profile_buf[address],
+                    });
+                }
+            }
+        }
+        mismatches.sort_by_key(|m| m.address);
+
+        Ok(WriteReport { mismatches })
+    }
+
+    /// Backs up the current profile to `backup` and writes `Profile::DEFAULTS`,
+    /// the defaults of this tool rather than Endgame Gear's, to the device. No
+    /// reset opcode is known yet, so the defaults are written as regular
+    /// profile on top of the current one, bytes not mapped in `MouseProfile`
+    /// keep their current value.
+    ///
+    /// * `backup`: file the current profile image is saved to before writing
+    pub fn factory_reset(&self, backup: &Path) -> Result<WriteReport> {
+        let mut profile = self.read_profile()?;
+        profile.save(backup)?;
+        profile.reset();
+        self.write_profile(&mut profile)
+    }
+
+    /// Fails with `Error::UnsupportedField` if `profile` differs from the
+    /// device in a field the firmware of `info` does not support, the device
+    /// is only read if there are such fields.
+    fn check_unsupported_fields(&self, profile: &Profile, info: &DeviceInfo) -> Result<()> {
+        let field_map = profile.profile_fields.hashmap();
+        let unsupported: Vec<_> = field_map
+            .values()
+            .filter(|attribute| !attribute.supports(Some(info.firmware)))
+            .collect();
+        if unsupported.is_empty() {
+            return Ok(());
+        }
+        let mut current: Profile = Profile::init();
+        self.read_profile_buf(&mut current.profile_buf)?;
+        for attribute in unsupported {
+            if attribute
+                .all_addresses()
+                .any(|address| profile.profile_buf[address] != current.profile_buf[address])
+            {
+                attribute.check_firmware(Some(info.firmware))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Requests the handshake and validates the response of the device
+    pub fn handshake(&self) -> Result<DeviceInfo> {
+        self.with_retries(false, || self.exchange_handshake())
+    }
+
+    /// Requests the profile and reads it into `buf`
+    fn read_profile_buf(&self, buf: &mut [u8]) -> Result<()> {
+        self.with_retries(true, || self.exchange_read_profile(buf))
+    }
+
+    /// Runs `exchange` and retries it according to the `TransferPolicy`.
+    /// Errors which can not be resolved by retrying are returned immediately.
+    ///
+    /// * `rehandshake`: whether the exchange needs a preceding handshake
+    /// * `exchange`: request/response exchange to run
+    fn with_retries<R>(
+        &self,
+        rehandshake: bool,
+        mut exchange: impl FnMut() -> Result<R>,
+    ) -> Result<R> {
+        let mut attempt: u32 = 0;
+        loop {
+            match exchange() {
+                Ok(res) => return Ok(res),
+                Err(e) if attempt < self.policy.retries && Self::is_transient(&e) => {
+                    attempt += 1;
+                    thread::sleep(self.policy.backoff);
+                    if rehandshake && self.policy.rehandshake {
+                        // a failing handshake shows up in the next attempt
+                        let _ = self.exchange_handshake();
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
 
-        todo!();
+    /// whether `error` may disappear when retrying the exchange, only
+    /// interrupted or incomplete transfers are retried
+    fn is_transient(error: &Error) -> bool {
+        matches!(
+            error,
+            Error::Timeout
+                | Error::ShortRead { .. }
+                | Error::ShortWrite { .. }
+                | Error::Usb(
+                    rusb::Error::Io
+                        | rusb::Error::Pipe
+                        | rusb::Error::Overflow
+                        | rusb::Error::Interrupted
+                )
+        )
+    }
+
+    /// single handshake exchange without retries
+    fn exchange_handshake(&self) -> Result<DeviceInfo> {
+        match self.exchange(Command::Handshake, &[])? {
+            Some(Response::DeviceInfo(info)) => Ok(info),
+            other => Err(Self::unexpected(Command::Handshake, other)),
+        }
+    }
+
+    /// single read profile exchange without retries
+    fn exchange_read_profile(&self, buf: &mut [u8]) -> Result<()> {
+        match self.exchange(Command::ReadProfile, &[])? {
+            Some(Response::Profile(profile)) => {
+                buf.copy_from_slice(profile.as_slice());
+                Ok(())
+            }
+            other => Err(Self::unexpected(Command::ReadProfile, other)),
+        }
+    }
+
+    /// Sends `command` with `payload` and reads and decodes its response,
+    /// `None` for commands without response.
+    fn exchange(&self, command: Command, payload: &[u8]) -> Result<Option<Response>> {
+        // Sending request
+        let request = command.request(payload)?;
+        let written = self
+            .transport
+            .send_feature_report(&request, self.policy.timeout)?;
+        if written != request.len() {
+            return Err(Error::ShortWrite {
+                expected: request.len(),
+                actual: written,
+            });
+        }
+        let Some(length) = command.response_length() else {
+            return Ok(None);
+        };
+
+        // Reading response
+        let mut response = vec![0; length];
+        let read = self
+            .transport
+            .get_feature_report(&mut response, self.policy.timeout)?;
+        response.truncate(read);
+        command.parse_response(&response).map(Some)
+    }
+
+    /// error for a response not matching `command`
+    fn unexpected(command: Command, response: Option<Response>) -> Error {
+        Error::Protocol(format!("unexpected response to {command:?}: {response:?}"))
     }
 }
 
-/// destructor closing down open connections on free
-impl Drop for Handler {
-    fn drop(&mut self) {
-        // releasing interface
-        self.handle
-            .release_interface(self::Handler::INTERFACE)
-            .expect("failed to release interface");
-        // reattaching kernel
-        self.handle
-            .attach_kernel_driver(self::Handler::INTERFACE)
-            .expect("failed to attach to kernel driver when closing");
+/// Single byte which differs between the written profile and the
+/// profile read back from the device
+///
+/// * `address`: position in the profile buffer
+/// * `field`: name of the attribute the address belongs to
+/// * `written`: value which was written to the device
+/// * `read`: value which was read back from the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteMismatch {
+    pub address: usize,
+    pub field: String,
+    pub written: u8,
+    pub read: u8,
+}
+
+/// Result of `Handler::write_profile`
+///
+/// * `mismatches`: all mapped bytes which differ after the write, sorted by address
+#[derive(Debug, Clone, Default)]
+pub struct WriteReport {
+    pub mismatches: Vec<ByteMismatch>,
+}
+impl WriteReport {
+    /// true if the device accepted every mapped byte
+    pub fn is_verified(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+    use crate::transport::simulated::SimulatedDevice;
+
+    /// simulated device failing the first `failures` requests of `command`
+    /// with `error`, the command byte of every sent request is logged
+    struct FlakyDevice {
+        device: SimulatedDevice,
+        command: u8,
+        failures: Cell<u32>,
+        error: fn() -> Error,
+        sent: RefCell<Vec<u8>>,
+    }
+    impl FlakyDevice {
+        fn new(command: Command, failures: u32, error: fn() -> Error) -> Handler<Self> {
+            let mut handler = Handler::new(Self {
+                device: SimulatedDevice::new(),
+                command: command.signature()[1],
+                failures: Cell::new(failures),
+                error,
+                sent: RefCell::new(Vec::new()),
+            });
+            handler.set_policy(TransferPolicy {
+                backoff: Duration::ZERO,
+                ..TransferPolicy::default()
+            });
+            handler
+        }
+    }
+    impl Transport for FlakyDevice {
+        fn send_feature_report(&self, data: &[u8], timeout: Duration) -> Result<usize> {
+            self.sent.borrow_mut().push(data[1]);
+            if data[1] == self.command && self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err((self.error)());
+            }
+            self.device.send_feature_report(data, timeout)
+        }
+
+        fn get_feature_report(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+            self.device.get_feature_report(buf, timeout)
+        }
+    }
+
+    #[test]
+    fn transient_errors_are_retried_after_a_handshake() {
+        let handler = FlakyDevice::new(Command::ReadProfile, 2, || Error::Timeout);
+        handler.read_profile().unwrap();
+        assert_eq!(
+            *handler.transport().sent.borrow(),
+            [0x02, 0x12, 0x02, 0x12, 0x02, 0x12]
+        );
+
+        let mut handler = FlakyDevice::new(Command::ReadProfile, 1, || Error::ShortRead {
+            expected: 1041,
+            actual: 64,
+        });
+        handler.set_policy(TransferPolicy {
+            rehandshake: false,
+            ..*handler.policy()
+        });
+        handler.read_profile().unwrap();
+        assert_eq!(*handler.transport().sent.borrow(), [0x02, 0x12, 0x12]);
+    }
+
+    #[test]
+    fn retries_are_limited_by_the_policy() {
+        let handler =
+            FlakyDevice::new(Command::Handshake, u32::MAX, || Error::Usb(rusb::Error::Io));
+        assert!(matches!(
+            handler.handshake(),
+            Err(Error::Usb(rusb::Error::Io))
+        ));
+        assert_eq!(*handler.transport().sent.borrow(), [0x02; 3]);
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let errors: [fn() -> Error; 6] = [
+            || Error::Protocol("profile writes are only supported by the usb backend".into()),
+            || Error::DeviceNotFound,
+            || Error::DeviceBusy { pid: None },
+            || Error::InvalidProfile("signature".into()),
+            || Error::HandshakeMismatch { response: vec![] },
+            || Error::Usb(rusb::Error::NotSupported),
+        ];
+        for error in errors {
+            let handler = FlakyDevice::new(Command::Handshake, u32::MAX, error);
+            let result = handler.handshake().unwrap_err();
+            assert_eq!(
+                *handler.transport().sent.borrow(),
+                [0x02],
+                "{result:?} was retried"
+            );
+        }
     }
 }
@@ -1,3 +1,15 @@
+pub mod capture;
+pub mod cleanup;
+pub mod command;
+pub mod device_info;
+pub mod error;
+pub mod explore;
+pub mod handler;
+pub mod lock;
+pub mod profile;
+pub mod transport;
+pub mod watch;
+
 // ripple_control: ProfileAttribute,
 // motion_sync: ProfileAttribute,
 // num_cpi_levels: ProfileAttribute,
@@ -0,0 +1,176 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/// Exclusive lock on a device shared by all processes of all users, held
+/// with flock(2) on a lock file keyed by the port path of the device. The
+/// file contains the PID of the holder, the lock is released on drop.
+/// Lock files live in `/run/lock`, falling back to `$XDG_RUNTIME_DIR` and the
+/// temp dir. They are opened without following symlinks and only a newly
+/// created file is made writable for all users, so a file planted by another
+/// user can not be used to change the permissions of an arbitrary file.
+///
+/// * `file`: locked file
+/// * `path`: path of the lock file
+pub struct DeviceLock {
+    file: File,
+    path: PathBuf,
+}
+impl DeviceLock {
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Acquires the lock of the device at `port_path`. Fails with
+    /// `Error::DeviceBusy` if another process holds it, or waits up to
+    /// `wait` for the lock to be released.
+    pub fn acquire(port_path: &str, wait: Option<Duration>) -> Result<Self> {
+        let path = Self::lock_path(port_path);
+        let file = Self::open(&path)?;
+        let deadline = wait.map(|wait| Instant::now() + wait);
+        loop {
+            // SAFETY: flock on a valid file descriptor
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+                break;
+            }
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(Error::file(&path, e));
+            }
+            if deadline.is_none_or(|deadline| Instant::now() >= deadline) {
+                return Err(Error::DeviceBusy {
+                    pid: Self::holder(&file),
+                });
+            }
+            thread::sleep(Self::POLL_INTERVAL);
+        }
+
+        let mut lock = Self { file, path };
+        // the pid is informational only, the lock is held even if it can not be written
+        let _ = lock.write_pid();
+        Ok(lock)
+    }
+
+    /// returns the path of the held lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// returns the path of the lock file of the device at `port_path`
+    pub fn lock_path(port_path: &str) -> PathBuf {
+        Self::lock_dir().join(format!("egg_xm28kv2-{port_path}.lock"))
+    }
+
+    /// directory of the lock files, `/run/lock` is shared by all users
+    fn lock_dir() -> PathBuf {
+        let run_lock = Path::new("/run/lock");
+        if run_lock.is_dir() {
+            return run_lock.to_path_buf();
+        }
+        env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_dir())
+            .unwrap_or_else(env::temp_dir)
+    }
+
+    /// Opens or creates the lock file without following symlinks. A created
+    /// file is made accessible for all users through its descriptor, existing
+    /// files keep their permissions.
+    fn open(path: &Path) -> Result<File> {
+        let options = |write: bool| {
+            let mut options = OpenOptions::new();
+            options
+                .read(true)
+                .write(write)
+                .custom_flags(libc::O_NOFOLLOW);
+            options
+        };
+        let file = match options(true).create_new(true).open(path) {
+            Ok(file) => {
+                let _ = file.set_permissions(fs::Permissions::from_mode(0o666));
+                file
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => options(true)
+                .open(path)
+                // a lock file created by another user may not be writable
+                .or_else(|_| options(false).open(path))
+                .map_err(|e| Error::file(path, e))?,
+            Err(e) => return Err(Error::file(path, e)),
+        };
+        let metadata = file.metadata().map_err(|e| Error::file(path, e))?;
+        if !metadata.is_file() {
+            return Err(Error::file(
+                path,
+                io::Error::other("lock file is not a regular file"),
+            ));
+        }
+        Ok(file)
+    }
+
+    /// reads the PID of the process holding the lock on `file`
+    fn holder(mut file: &File) -> Option<u32> {
+        let mut content = String::new();
+        file.rewind().ok()?;
+        file.read_to_string(&mut content).ok()?;
+        content.trim().parse().ok()
+    }
+
+    fn write_pid(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        writeln!(self.file, "{}", std::process::id())
+    }
+}
+impl Drop for DeviceLock {
+    fn drop(&mut self) {
+        // clearing the pid, the lock itself is released when the file is closed
+        let _ = self.file.set_len(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    #[test]
+    fn second_acquire_is_busy() {
+        let port_path = format!("test-busy-{}", std::process::id());
+        let lock = DeviceLock::acquire(&port_path, None).unwrap();
+        assert!(matches!(
+            DeviceLock::acquire(&port_path, None),
+            Err(Error::DeviceBusy { pid: Some(pid) }) if pid == std::process::id()
+        ));
+        assert!(matches!(
+            DeviceLock::acquire(&port_path, Some(Duration::from_millis(100))),
+            Err(Error::DeviceBusy { .. })
+        ));
+        let path = lock.path().to_path_buf();
+        drop(lock);
+        drop(DeviceLock::acquire(&port_path, None).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn symlinked_lock_file_is_not_followed() {
+        let port_path = format!("test-symlink-{}", std::process::id());
+        let target = env::temp_dir().join(format!("egg_xm28kv2-{port_path}.target"));
+        File::create(&target).unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        let path = DeviceLock::lock_path(&port_path);
+        symlink(&target, &path).unwrap();
+
+        let result = DeviceLock::acquire(&port_path, None);
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&target).unwrap();
+        assert!(matches!(result, Err(Error::File { .. })));
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -1,10 +1,463 @@
-pub mod handler;
-pub mod profile;
-use crate::handler::Handler;
-use crate::profile::profile_base::Profile;
-
-fn main() {
-    let handler = Handler::init();
-    let profile = handler.read_profile().unwrap();
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use egg_xm28kv2::capture::{self, CaptureDirection};
+use egg_xm28kv2::cleanup;
+use egg_xm28kv2::error::{Error, Result};
+use egg_xm28kv2::explore::Finding;
+use egg_xm28kv2::handler::{Handler, TransferPolicy, WriteReport};
+use egg_xm28kv2::profile::button_action::{ButtonAction, ProgrammableButton};
+use egg_xm28kv2::profile::keys::KeyboardLayout;
+use egg_xm28kv2::profile::profile_base::Profile;
+use egg_xm28kv2::profile::settings::CpiStage;
+use egg_xm28kv2::transport::Transport;
+use egg_xm28kv2::transport::hidraw::HidrawTransport;
+use egg_xm28kv2::transport::recording::{RecordingTransport, ReplayTransport};
+use egg_xm28kv2::transport::usb::{DeviceSelector, UsbTransport};
+use egg_xm28kv2::watch::{WatchEvent, Watcher};
+
+/// Configurator for the Endgame Gear XM2 8k v2
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// device to use if several mice are connected:
+    /// BUS:ADDRESS, BUS-PORT[.PORT] or serial:SERIAL (see `list`)
+    #[arg(long, global = true)]
+    device: Option<DeviceSelector>,
+
+    /// timeout of a single transfer in milliseconds
+    #[arg(long, global = true, default_value_t = 1000)]
+    timeout_ms: u64,
+
+    /// number of retries after a timed out or incomplete transfer
+    #[arg(long, global = true, default_value_t = 2)]
+    retries: u32,
+
+    /// pause before every retry in milliseconds
+    #[arg(long, global = true, default_value_t = 100)]
+    backoff_ms: u64,
+
+    /// do not repeat the handshake before retrying a request
+    #[arg(long, global = true)]
+    no_rehandshake: bool,
+
+    /// wait up to MS milliseconds if another instance holds the device
+    #[arg(long, global = true, value_name = "MS")]
+    wait_lock: Option<u64>,
+
+    /// log every transfer to FILE
+    #[arg(long, global = true, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// usb detaches the kernel driver while configuring, hidraw keeps it
+    /// attached but cannot write profiles yet and ignores `--device`
+    #[arg(long, global = true, value_enum, default_value_t = Backend::Usb)]
+    backend: Backend,
+
+    /// replay the transfers recorded in FILE instead of using a mouse
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "device")]
+    replay: Option<PathBuf>,
+
+    /// keyboard layout key bindings are given and shown in: us, de, fr, gb
+    /// or auto for the active XKB layout (default: US key positions), only
+    /// these built-in tables are used and other variants such as us(dvorak)
+    /// are rejected, the XKB keymap itself is not translated
+    #[arg(long, global = true)]
+    layout: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// transport used to talk to the mouse
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    Usb,
+    Hidraw,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// list all connected mice
+    List,
+    /// read and print the current profile (default)
+    Read,
+    /// print firmware and protocol version reported by the mouse
+    Info,
+    /// reattach the kernel driver of a mouse left detached by a crashed run
+    Recover,
+    /// save the current profile to a file
+    Save { file: PathBuf },
+    /// write a profile file to the mouse and verify it
+    Write { file: PathBuf },
+    /// set the resolution of a CPI stage, e.g. `dpi 2 1600` or `dpi 2 800x1600` for X and Y
+    Dpi {
+        /// CPI stage, 1 to 4
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=4))]
+        stage: u8,
+        /// DPI for both axes or XxY
+        value: CpiStage,
+    },
+    /// bind an action to a button, e.g. `map forward key:Ctrl+C`
+    Map {
+        /// right, middle, forward, back, wheel-up or wheel-down
+        button: ProgrammableButton,
+        /// left-click, right-click, middle-click, forward, back, scroll-up,
+        /// scroll-down, cpi-loop, fixed-cpi:DPI, play-pause, next, previous,
+        /// mute, volume-up, volume-down, browser, explorer, disable or
+        /// key:[MODIFIER+]KEY with the modifiers Ctrl, Shift, Alt and Win and
+        /// a HID key name such as A, F5, Page Up, Keypad 1 or Escape,
+        /// with `--layout` the character typed on that layout such as Z or ß
+        action: String,
+    },
+    /// back up the current profile and write the defaults of this tool
+    /// (not the Endgame Gear factory settings)
+    Reset {
+        /// file to save the current profile to,
+        /// defaults to profile-backup-<UNIX TIME>.bin
+        #[arg(long, value_name = "FILE")]
+        backup: Option<PathBuf>,
+        /// do not ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// apply a profile file to every mouse when it is connected
+    Watch { file: PathBuf },
+    /// find the profile bytes belonging to an option by changing it step by step
+    Explore {
+        /// append the findings as JSON lines to FILE
+        #[arg(long, value_name = "FILE", default_value = "explore.jsonl")]
+        log: PathBuf,
+    },
+    /// decode the profiles transferred in a usbmon/USBPcap pcap or pcapng capture
+    Import {
+        capture: PathBuf,
+        /// save every profile found as profile file to DIR
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+/// prints `error` and its hint to stderr
+fn report(error: &Error) {
+    eprintln!("error: {error}");
+    if let Some(hint) = error.hint() {
+        eprintln!("hint: {hint}");
+    }
+}
+
+/// prints the verification result of a profile write
+fn print_write_report(report: &WriteReport) {
+    if report.is_verified() {
+        println!("profile written and verified");
+        return;
+    }
+    println!(
+        "profile written, {} bytes differ after readback:",
+        report.mismatches.len()
+    );
+    for mismatch in &report.mismatches {
+        println!(
+            "  [{}] {}: wrote {:#04x}, read {:#04x}",
+            mismatch.address, mismatch.field, mismatch.written, mismatch.read
+        );
+    }
+}
+
+/// transfer policy given by the command line options
+fn policy(cli: &Cli) -> TransferPolicy {
+    TransferPolicy {
+        timeout: Duration::from_millis(cli.timeout_ms),
+        retries: cli.retries,
+        backoff: Duration::from_millis(cli.backoff_ms),
+        rehandshake: !cli.no_rehandshake,
+    }
+}
+
+/// lock wait given by `--wait-lock`
+fn lock_wait(cli: &Cli) -> Option<Duration> {
+    cli.wait_lock.map(Duration::from_millis)
+}
+
+/// keyboard layout given by `--layout`, `auto` detects the active layout
+fn layout(cli: &Cli) -> Result<Option<KeyboardLayout>> {
+    let layout = match cli.layout.as_deref() {
+        None => return Ok(None),
+        Some("auto") => KeyboardLayout::detect(),
+        Some(name) => name.parse(),
+    };
+    layout.map(Some).map_err(|message| Error::InvalidValue {
+        field: "keyboard layout".into(),
+        message,
+    })
+}
+
+/// opens the device chosen by `--device`, the first connected one otherwise
+/// through `--backend` or the recording given by `--replay`, wrapped by `--record`
+fn open(cli: &Cli) -> Result<Handler<Box<dyn Transport>>> {
+    open_session(cli, 0)
+}
+
+/// Opens the device like `open` for the `session`-th time of the command,
+/// later sessions are appended to `--record` and replayed from the matching
+/// session of `--replay`.
+fn open_session(cli: &Cli, session: usize) -> Result<Handler<Box<dyn Transport>>> {
+    let mut transport: Box<dyn Transport> = match (&cli.replay, cli.backend, &cli.device) {
+        (Some(file), _, _) => Box::new(ReplayTransport::open_session(file, session)?),
+        (None, Backend::Usb, selector) => {
+            Box::new(UsbTransport::open(selector.as_ref(), lock_wait(cli))?)
+        }
+        (None, Backend::Hidraw, None) => Box::new(HidrawTransport::init(lock_wait(cli))?),
+        (None, Backend::Hidraw, Some(_)) => {
+            return Err(Error::InvalidValue {
+                field: "--device".into(),
+                message: "not supported by the hidraw backend".into(),
+            });
+        }
+    };
+    transport = match &cli.record {
+        Some(file) if session == 0 => Box::new(RecordingTransport::new(transport, file)?),
+        Some(file) => Box::new(RecordingTransport::append(transport, file)?),
+        None => transport,
+    };
+    let mut handler = Handler::new(transport);
+    handler.set_policy(policy(cli));
+    Ok(handler)
+}
+
+fn list(cli: &Cli) -> Result<()> {
+    let devices: Vec<_> = Handler::list()?
+        .into_iter()
+        .filter(|device| cli.device.as_ref().is_none_or(|s| s.matches(device)))
+        .collect();
+    if devices.is_empty() {
+        println!("no device connected");
+    }
+    for device in devices {
+        println!("{device}");
+    }
+    Ok(())
+}
+
+fn read(cli: &Cli) -> Result<()> {
+    let layout = layout(cli)?;
+    let handler = open(cli)?;
+    let mut profile = handler.read_profile()?;
+    profile.layout = layout;
     profile.print_profile();
+    Ok(())
+}
+
+fn info(cli: &Cli) -> Result<()> {
+    let handler = open(cli)?;
+    let info = handler.handshake()?;
+    println!("{info}");
+    Ok(())
+}
+
+fn recover(cli: &Cli) -> Result<()> {
+    if Handler::recover(cli.device.as_ref())? {
+        println!("kernel driver reattached");
+    } else {
+        println!("kernel driver is attached, nothing to recover");
+    }
+    Ok(())
+}
+
+fn save(cli: &Cli, file: &Path) -> Result<()> {
+    let handler = open(cli)?;
+    let profile = handler.read_profile()?;
+    profile.save(file)?;
+    println!("profile saved to {}", file.display());
+    Ok(())
+}
+
+fn write(cli: &Cli, file: &Path) -> Result<()> {
+    let mut profile = Profile::load(file)?;
+    let handler = open(cli)?;
+    let report = handler.write_profile(&mut profile)?;
+    print_write_report(&report);
+    Ok(())
+}
+
+fn dpi(cli: &Cli, stage: u8, value: CpiStage) -> Result<()> {
+    let handler = open(cli)?;
+    let mut profile = handler.read_profile()?;
+    profile.set_cpi_stage(stage.into(), value)?;
+    let report = handler.write_profile(&mut profile)?;
+    println!("CPI stage {stage}: {value}");
+    print_write_report(&report);
+    Ok(())
+}
+
+fn map(cli: &Cli, button: ProgrammableButton, action: &str) -> Result<()> {
+    let layout = layout(cli)?;
+    let action = ButtonAction::parse_in(action, layout).map_err(|message| Error::InvalidValue {
+        field: "action".into(),
+        message,
+    })?;
+    let handler = open(cli)?;
+    let mut profile = handler.read_profile()?;
+    profile.set_button_action(button, action)?;
+    let report = handler.write_profile(&mut profile)?;
+    println!("{button:?}: {}", action.format(layout));
+    print_write_report(&report);
+    Ok(())
+}
+
+/// asks `question` on stdout, true if answered with y or yes
+fn confirm(question: &str) -> Result<bool> {
+    print!("{question} [y/N] ");
+    io::stdout().flush().map_err(Error::Io)?;
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(Error::Io)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn reset(cli: &Cli, backup: Option<&Path>, yes: bool) -> Result<()> {
+    let backup = backup.map(Path::to_path_buf).unwrap_or_else(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        PathBuf::from(format!("profile-backup-{}.bin", now.as_secs()))
+    });
+    if !yes
+        && !confirm(&format!(
+            "reset all settings to the defaults of this tool, not the Endgame Gear \
+             factory settings (backup in {})?",
+            backup.display()
+        ))?
+    {
+        println!("aborted");
+        return Ok(());
+    }
+    let handler = open(cli)?;
+    let report = handler.factory_reset(&backup)?;
+    println!("previous profile saved to {}", backup.display());
+    print_write_report(&report);
+    Ok(())
+}
+
+fn watch(cli: &Cli, file: &Path) -> Result<()> {
+    let mut watcher = Watcher::new(Profile::load(file)?);
+    watcher.selector = cli.device.clone();
+    watcher.policy = policy(cli);
+    if cli.wait_lock.is_some() {
+        watcher.lock_wait = lock_wait(cli);
+    }
+    println!("watching for mice, applying {}", file.display());
+    watcher.run(|event| match event {
+        WatchEvent::Arrived(entry) => println!("connected: {entry}"),
+        WatchEvent::Applied(entry, report) => {
+            print!("{entry}: ");
+            print_write_report(&report);
+        }
+        WatchEvent::Failed(entry, e) => {
+            eprint!("{entry}: ");
+            report(&e);
+        }
+        WatchEvent::Left(entry) => println!("disconnected: {entry}"),
+    })
+}
+
+fn import(cli: &Cli, capture: &Path, out_dir: Option<&Path>) -> Result<()> {
+    let layout = layout(cli)?;
+    let profiles = capture::import(capture)?;
+    if profiles.is_empty() {
+        println!("no profile transfers found in {}", capture.display());
+    }
+    for (index, captured) in profiles.iter().enumerate() {
+        let direction = match captured.direction {
+            CaptureDirection::Read => "read",
+            CaptureDirection::Write => "write",
+        };
+        println!(
+            "#{index} {direction} at {:.6}s",
+            captured.timestamp.as_secs_f64()
+        );
+        let mut profile = captured.profile();
+        profile.layout = layout;
+        profile.print_profile();
+        if let Some(dir) = out_dir {
+            let file = dir.join(format!("profile_{index:03}_{direction}.bin"));
+            profile.save(&file)?;
+            println!("saved to {}", file.display());
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn explore(cli: &Cli, log: &Path) -> Result<()> {
+    // the device is reopened for every snapshot as it may be replugged in
+    // between, every snapshot is a session of --record and --replay
+    let mut before = open_session(cli, 0)?.read_profile()?;
+    let stdin = io::stdin();
+    let mut session = 0;
+    loop {
+        print!(
+            "change a single option (in the vendor software or on another machine), \
+             then describe the change and press Enter (empty to quit): "
+        );
+        io::stdout().flush().map_err(Error::Io)?;
+        let mut label = String::new();
+        stdin.lock().read_line(&mut label).map_err(Error::Io)?;
+        let label = label.trim();
+        if label.is_empty() {
+            return Ok(());
+        }
+
+        session += 1;
+        let after = open_session(cli, session)?.read_profile()?;
+        let finding = Finding::new(label, &before, &after);
+        if finding.changes.is_empty() {
+            println!("no bytes changed");
+        }
+        for change in &finding.changes {
+            println!(
+                "  [{}] {:#04x} -> {:#04x} ({})",
+                change.address,
+                change.before,
+                change.after,
+                change.claimed_by.as_deref().unwrap_or("unknown")
+            );
+        }
+        finding.append_to_log(log)?;
+        before = after;
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(e) = cleanup::install() {
+        report(&e);
+        return ExitCode::FAILURE;
+    }
+    let result = match &cli.command {
+        Some(Command::List) => list(&cli),
+        Some(Command::Read) | None => read(&cli),
+        Some(Command::Info) => info(&cli),
+        Some(Command::Recover) => recover(&cli),
+        Some(Command::Save { file }) => save(&cli, file),
+        Some(Command::Write { file }) => write(&cli, file),
+        Some(Command::Dpi { stage, value }) => dpi(&cli, *stage, *value),
+        Some(Command::Map { button, action }) => map(&cli, *button, action),
+        Some(Command::Reset { backup, yes }) => reset(&cli, backup.as_deref(), *yes),
+        Some(Command::Watch { file }) => watch(&cli, file),
+        Some(Command::Explore { log }) => explore(&cli, log),
+        Some(Command::Import { capture, out_dir }) => import(&cli, capture, out_dir.as_deref()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            report(&e);
+            ExitCode::FAILURE
+        }
+    }
 }
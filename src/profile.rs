@@ -1,4 +1,30 @@
+pub mod button_action;
+pub mod keys;
 pub mod profile_attribute;
 pub mod profile_attribute_args;
 pub mod profile_base;
 pub mod profile_fields;
+pub mod settings;
+
+/// Characters separating the words of a name, e.g. `page-up`, `wheel_up` or
+/// `Play/Pause`
+pub(crate) const NAME_SEPARATORS: &[char] = &['-', '_', '/'];
+
+/// Lowercase `name` without whitespace and without `separators` between two
+/// letters or digits, used to compare names given by the user (`Fixed CPI`,
+/// `fixed-cpi`). Separators next to anything else are kept, so `Keypad -`,
+/// `Keypad +/-` and `-5` stay distinct.
+pub(crate) fn normalize(name: &str, separators: &[char]) -> String {
+    let chars: Vec<char> = name.chars().filter(|c| !c.is_whitespace()).collect();
+    chars
+        .iter()
+        .enumerate()
+        .filter(|&(i, c)| {
+            let between_words = i > 0
+                && chars[i - 1].is_alphanumeric()
+                && chars.get(i + 1).is_some_and(|next| next.is_alphanumeric());
+            !(separators.contains(c) && between_words)
+        })
+        .flat_map(|(_, c)| c.to_lowercase())
+        .collect()
+}
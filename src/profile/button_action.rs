@@ -0,0 +1,273 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::profile::keys::{KeyCombo, KeyboardLayout, Modifiers};
+use crate::profile::{
+    NAME_SEPARATORS, normalize,
+    profile_attribute::ProfileAttribute,
+    profile_fields::{MouseProfile, ProfileFieldName},
+    settings::CpiStage,
+};
+
+/// Buttons which can be remapped, see sniffed_protocol.md
+/// parsed from `right`, `middle`, `forward`, `back`, `wheel-up` and `wheel-down`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProgrammableButton {
+    Right,
+    Middle,
+    Forward,
+    Back,
+    WheelUp,
+    WheelDown,
+}
+impl ProgrammableButton {
+    pub const ALL: [Self; 6] = [
+        Self::Right,
+        Self::Middle,
+        Self::Forward,
+        Self::Back,
+        Self::WheelUp,
+        Self::WheelDown,
+    ];
+
+    /// whether the button may be mapped to `ButtonAction::FixedCpi`
+    pub const fn supports_fixed_cpi(self) -> bool {
+        !matches!(self, Self::WheelUp | Self::WheelDown)
+    }
+
+    /// name of the action field of the button
+    pub const fn field(self) -> ProfileFieldName {
+        match self {
+            Self::Right => ProfileFieldName::RightBtnAction,
+            Self::Middle => ProfileFieldName::MidBtnAction,
+            Self::Forward => ProfileFieldName::ForwardBtnAction,
+            Self::Back => ProfileFieldName::BackBtnAction,
+            Self::WheelUp => ProfileFieldName::WheelUpAction,
+            Self::WheelDown => ProfileFieldName::WheelDownAction,
+        }
+    }
+
+    /// button whose action is stored in `field`
+    pub fn from_field(field: &ProfileFieldName) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|button| button.field() == *field)
+    }
+
+    /// action attribute of the button
+    pub(crate) fn attribute(self, fields: &MouseProfile) -> &ProfileAttribute {
+        match self {
+            Self::Right => &fields.right_btn_action,
+            Self::Middle => &fields.mid_btn_action,
+            Self::Forward => &fields.forward_btn_action,
+            Self::Back => &fields.back_btn_action,
+            Self::WheelUp => &fields.wheel_up_action,
+            Self::WheelDown => &fields.wheel_down_action,
+        }
+    }
+}
+impl FromStr for ProgrammableButton {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s, NAME_SEPARATORS).as_str() {
+            "right" => Ok(Self::Right),
+            "middle" | "mid" => Ok(Self::Middle),
+            "forward" => Ok(Self::Forward),
+            "back" => Ok(Self::Back),
+            "wheelup" => Ok(Self::WheelUp),
+            "wheeldown" => Ok(Self::WheelDown),
+            _ => Err(format!(
+                "unknown button `{s}`, expected right, middle, forward, back, wheel-up or wheel-down"
+            )),
+        }
+    }
+}
+
+/// Action bound to a `ProgrammableButton`, stored as two action bytes
+/// followed by four data field bytes
+/// - mouse buttons, scrolling, CPI loop, media keys, Browser, Explorer and Disable
+///   are fixed codes
+/// - FixedCpi: 0xc0 0x00, the data field holds the CPI stage (X and Y little endian)
+/// - Key: 0x02 and the modifier bits, the first data field byte holds the HID usage ID
+/// - Unknown: any other code, kept as is
+///
+/// displayed as e.g. `Forward`, `Fixed CPI: 800dpi` or `Key: Ctrl+C` and
+/// parsed from these or from forms such as `forward`, `fixed-cpi:800x1600`
+/// and `key:ctrl+c`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ButtonAction {
+    LeftClick,
+    RightClick,
+    MiddleClick,
+    Forward,
+    Back,
+    ScrollUp,
+    ScrollDown,
+    CpiLoop,
+    FixedCpi(CpiStage),
+    PlayPause,
+    Next,
+    Previous,
+    Mute,
+    VolumeUp,
+    VolumeDown,
+    Browser,
+    Explorer,
+    Disable,
+    Key(KeyCombo),
+    Unknown { action: [u8; 2], data: [u8; 4] },
+}
+impl ButtonAction {
+    const FIXED_CPI: [u8; 2] = [0xc0, 0x00];
+    const KEY: u8 = 0x02;
+
+    /// actions with fixed codes as (action, action bytes, data field, name)
+    const FIXED: [(Self, [u8; 2], [u8; 4], &'static str); 17] = [
+        (Self::LeftClick, [0x00, 0x01], [0; 4], "Left Click"),
+        (Self::RightClick, [0x00, 0x02], [0; 4], "Right Click"),
+        (Self::MiddleClick, [0x00, 0x04], [0; 4], "Middle Click"),
+        (Self::Forward, [0x00, 0x10], [0; 4], "Forward"),
+        (Self::Back, [0x00, 0x08], [0; 4], "Back"),
+        (Self::ScrollUp, [0x01, 0x01], [0; 4], "Scroll Up"),
+        (Self::ScrollDown, [0x01, 0xff], [0; 4], "Scroll Down"),
+        (Self::CpiLoop, [0x09, 0xf1], [0; 4], "CPI Loop"),
+        (Self::PlayPause, [0x20, 0xcd], [0; 4], "Play/Pause"),
+        (Self::Next, [0x20, 0xb5], [0; 4], "Next"),
+        (Self::Previous, [0x20, 0xb6], [0; 4], "Previous"),
+        (Self::Mute, [0x20, 0xe2], [0; 4], "Mute"),
+        (Self::VolumeUp, [0x20, 0xe9], [0; 4], "Volume Up"),
+        (Self::VolumeDown, [0x20, 0xea], [0; 4], "Volume Down"),
+        (Self::Browser, [0x18, 0x96], [0x01, 0, 0, 0], "Browser"),
+        (Self::Explorer, [0x18, 0x94], [0x01, 0, 0, 0], "Explorer"),
+        (Self::Disable, [0xff, 0x00], [0; 4], "Disable"),
+    ];
+
+    /// decodes the six bytes of an action, two action bytes and the data field
+    pub fn decode(bytes: &[u8]) -> Self {
+        let action = [bytes[0], bytes[1]];
+        let data = [bytes[2], bytes[3], bytes[4], bytes[5]];
+        if action == Self::FIXED_CPI {
+            if let Ok(stage) = CpiStage::from_bytes(&data) {
+                return Self::FixedCpi(stage);
+            }
+        } else if action[0] == Self::KEY {
+            return Self::Key(KeyCombo {
+                modifiers: Modifiers(action[1]),
+                usage: data[0],
+            });
+        } else if let Some((fixed, ..)) = Self::FIXED.iter().find(|(_, code, _, _)| *code == action)
+        {
+            return *fixed;
+        }
+        Self::Unknown { action, data }
+    }
+
+    /// encodes the action into two action bytes followed by the data field
+    pub fn encode(&self) -> [u8; 6] {
+        let (action, data) = match self {
+            Self::FixedCpi(stage) => (Self::FIXED_CPI, stage.to_bytes()),
+            Self::Key(combo) => ([Self::KEY, combo.modifiers.0], [combo.usage, 0, 0, 0]),
+            Self::Unknown { action, data } => (*action, *data),
+            fixed => Self::FIXED
+                .iter()
+                .find(|(action, ..)| action == fixed)
+                .map(|(_, action, data, _)| (*action, *data))
+                .unwrap(),
+        };
+        [action[0], action[1], data[0], data[1], data[2], data[3]]
+    }
+
+    /// Parses `s` like `from_str`, with `layout` the key of `key:KEY` is the
+    /// character it types on that layout, see `KeyCombo::parse_in`.
+    pub fn parse_in(s: &str, layout: Option<KeyboardLayout>) -> Result<Self, String> {
+        match s.split_once(':') {
+            Some((name, combo)) if normalize(name, NAME_SEPARATORS) == "key" => {
+                Ok(Self::Key(KeyCombo::parse_in(combo.trim(), layout)?))
+            }
+            _ => s.parse(),
+        }
+    }
+
+    /// Formats the action like `Display`, with `layout` keys are shown as
+    /// the character they type on that layout.
+    pub fn format(&self, layout: Option<KeyboardLayout>) -> String {
+        match self {
+            Self::Key(combo) => format!("{}: {}", self.name(), combo.format(layout)),
+            _ => self.to_string(),
+        }
+    }
+
+    /// name of the action without its argument
+    fn name(&self) -> &'static str {
+        match self {
+            Self::FixedCpi(_) => "Fixed CPI",
+            Self::Key(_) => "Key",
+            Self::Unknown { .. } => "Unknown",
+            fixed => Self::FIXED
+                .iter()
+                .find(|(action, ..)| action == fixed)
+                .map(|(.., name)| *name)
+                .unwrap(),
+        }
+    }
+}
+impl fmt::Display for ButtonAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FixedCpi(stage) => write!(f, "{}: {stage}", self.name()),
+            Self::Key(combo) => write!(f, "{}: {combo}", self.name()),
+            Self::Unknown { action, data } => {
+                write!(f, "{}: {action:02X?} {data:02X?}", self.name())
+            }
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+impl FromStr for ButtonAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.split_once(':') {
+            Some((name, argument)) => (normalize(name, NAME_SEPARATORS), Some(argument.trim())),
+            None => (normalize(s, NAME_SEPARATORS), None),
+        };
+        match (name.as_str(), argument) {
+            ("fixedcpi", Some(stage)) => Ok(Self::FixedCpi(stage.parse()?)),
+            ("key", Some(combo)) => Ok(Self::Key(combo.parse()?)),
+            (name, None) => Self::FIXED
+                .iter()
+                .find(|(.., fixed)| normalize(fixed, NAME_SEPARATORS) == name)
+                .map(|(action, ..)| *action)
+                .ok_or_else(|| {
+                    format!("unknown action `{s}`, see `--help` for the available actions")
+                }),
+            _ => Err(format!(
+                "invalid action `{s}`, only fixed-cpi:DPI and key:KEY take an argument"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_names_follow_one_normalization() {
+        for (input, action) in [
+            ("fixed-cpi: 800", "Fixed CPI: 800"),
+            ("play-pause", "Play/Pause"),
+            ("Play_Pause", "Play/Pause"),
+            ("key:page-up", "Key: Page Up"),
+            ("key: page_up", "Key: Page Up"),
+        ] {
+            let parsed = ButtonAction::parse_in(input, None).unwrap();
+            assert_eq!(
+                parsed,
+                ButtonAction::parse_in(action, None).unwrap(),
+                "{input}"
+            );
+        }
+    }
+}
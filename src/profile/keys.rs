@@ -0,0 +1,834 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::profile::{NAME_SEPARATORS, normalize};
+
+/// Modifier bits of a keyboard key binding ("decorator" in sniffed_protocol.md),
+/// combinations are the bitwise or of the single modifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers(pub u8);
+impl Modifiers {
+    pub const NONE: Self = Self(0x00);
+    pub const CTRL: Self = Self(0x01);
+    pub const SHIFT: Self = Self(0x02);
+    pub const ALT: Self = Self(0x04);
+    pub const WIN: Self = Self(0x08);
+
+    /// all modifiers with their display name, in display order
+    const NAMES: [(Self, &'static str); 4] = [
+        (Self::CTRL, "Ctrl"),
+        (Self::SHIFT, "Shift"),
+        (Self::ALT, "Alt"),
+        (Self::WIN, "Win"),
+    ];
+
+    /// whether all bits of `other` are set
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// modifier named `name`, case insensitive, or raw modifier bits
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(bits) = parse_hex(name) {
+            return Some(Self(bits));
+        }
+        match name.to_lowercase().as_str() {
+            "ctrl" | "control" => Some(Self::CTRL),
+            "shift" => Some(Self::SHIFT),
+            "alt" => Some(Self::ALT),
+            "win" | "super" | "meta" | "gui" => Some(Self::WIN),
+            _ => None,
+        }
+    }
+}
+impl std::ops::BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// HID usage IDs of the keyboard/keypad usage page (0x07) with their names,
+/// the letters and digits are named after the keys of the US layout
+const KEY_USAGES: [(u8, &str); 215] = [
+    (0x04, "A"),
+    (0x05, "B"),
+    (0x06, "C"),
+    (0x07, "D"),
+    (0x08, "E"),
+    (0x09, "F"),
+    (0x0a, "G"),
+    (0x0b, "H"),
+    (0x0c, "I"),
+    (0x0d, "J"),
+    (0x0e, "K"),
+    (0x0f, "L"),
+    (0x10, "M"),
+    (0x11, "N"),
+    (0x12, "O"),
+    (0x13, "P"),
+    (0x14, "Q"),
+    (0x15, "R"),
+    (0x16, "S"),
+    (0x17, "T"),
+    (0x18, "U"),
+    (0x19, "V"),
+    (0x1a, "W"),
+    (0x1b, "X"),
+    (0x1c, "Y"),
+    (0x1d, "Z"),
+    (0x1e, "1"),
+    (0x1f, "2"),
+    (0x20, "3"),
+    (0x21, "4"),
+    (0x22, "5"),
+    (0x23, "6"),
+    (0x24, "7"),
+    (0x25, "8"),
+    (0x26, "9"),
+    (0x27, "0"),
+    (0x28, "Enter"),
+    (0x29, "Escape"),
+    (0x2a, "Backspace"),
+    (0x2b, "Tab"),
+    (0x2c, "Space"),
+    (0x2d, "Minus"),
+    (0x2e, "Equal"),
+    (0x2f, "Left Bracket"),
+    (0x30, "Right Bracket"),
+    (0x31, "Backslash"),
+    (0x32, "Non-US #"),
+    (0x33, "Semicolon"),
+    (0x34, "Apostrophe"),
+    (0x35, "Grave"),
+    (0x36, "Comma"),
+    (0x37, "Period"),
+    (0x38, "Slash"),
+    (0x39, "Caps Lock"),
+    (0x3a, "F1"),
+    (0x3b, "F2"),
+    (0x3c, "F3"),
+    (0x3d, "F4"),
+    (0x3e, "F5"),
+    (0x3f, "F6"),
+    (0x40, "F7"),
+    (0x41, "F8"),
+    (0x42, "F9"),
+    (0x43, "F10"),
+    (0x44, "F11"),
+    (0x45, "F12"),
+    (0x46, "Print Screen"),
+    (0x47, "Scroll Lock"),
+    (0x48, "Pause"),
+    (0x49, "Insert"),
+    (0x4a, "Home"),
+    (0x4b, "Page Up"),
+    (0x4c, "Delete"),
+    (0x4d, "End"),
+    (0x4e, "Page Down"),
+    (0x4f, "Right"),
+    (0x50, "Left"),
+    (0x51, "Down"),
+    (0x52, "Up"),
+    (0x53, "Num Lock"),
+    (0x54, "Keypad /"),
+    (0x55, "Keypad *"),
+    (0x56, "Keypad -"),
+    (0x57, "Keypad +"),
+    (0x58, "Keypad Enter"),
+    (0x59, "Keypad 1"),
+    (0x5a, "Keypad 2"),
+    (0x5b, "Keypad 3"),
+    (0x5c, "Keypad 4"),
+    (0x5d, "Keypad 5"),
+    (0x5e, "Keypad 6"),
+    (0x5f, "Keypad 7"),
+    (0x60, "Keypad 8"),
+    (0x61, "Keypad 9"),
+    (0x62, "Keypad 0"),
+    (0x63, "Keypad ."),
+    (0x64, "Non-US \\"),
+    (0x65, "Application"),
+    (0x66, "Power"),
+    (0x67, "Keypad ="),
+    (0x68, "F13"),
+    (0x69, "F14"),
+    (0x6a, "F15"),
+    (0x6b, "F16"),
+    (0x6c, "F17"),
+    (0x6d, "F18"),
+    (0x6e, "F19"),
+    (0x6f, "F20"),
+    (0x70, "F21"),
+    (0x71, "F22"),
+    (0x72, "F23"),
+    (0x73, "F24"),
+    (0x74, "Execute"),
+    (0x75, "Help"),
+    (0x76, "Menu"),
+    (0x77, "Select"),
+    (0x78, "Stop"),
+    (0x79, "Again"),
+    (0x7a, "Undo"),
+    (0x7b, "Cut"),
+    (0x7c, "Copy"),
+    (0x7d, "Paste"),
+    (0x7e, "Find"),
+    (0x7f, "Mute"),
+    (0x80, "Volume Up"),
+    (0x81, "Volume Down"),
+    (0x82, "Locking Caps Lock"),
+    (0x83, "Locking Num Lock"),
+    (0x84, "Locking Scroll Lock"),
+    (0x85, "Keypad Comma"),
+    (0x86, "Keypad Equal Sign"),
+    (0x87, "International 1"),
+    (0x88, "International 2"),
+    (0x89, "International 3"),
+    (0x8a, "International 4"),
+    (0x8b, "International 5"),
+    (0x8c, "International 6"),
+    (0x8d, "International 7"),
+    (0x8e, "International 8"),
+    (0x8f, "International 9"),
+    (0x90, "Lang 1"),
+    (0x91, "Lang 2"),
+    (0x92, "Lang 3"),
+    (0x93, "Lang 4"),
+    (0x94, "Lang 5"),
+    (0x95, "Lang 6"),
+    (0x96, "Lang 7"),
+    (0x97, "Lang 8"),
+    (0x98, "Lang 9"),
+    (0x99, "Alternate Erase"),
+    (0x9a, "SysReq"),
+    (0x9b, "Cancel"),
+    (0x9c, "Clear"),
+    (0x9d, "Prior"),
+    (0x9e, "Alternate Return"),
+    (0x9f, "Separator"),
+    (0xa0, "Out"),
+    (0xa1, "Oper"),
+    (0xa2, "Clear/Again"),
+    (0xa3, "CrSel"),
+    (0xa4, "ExSel"),
+    (0xb0, "Keypad 00"),
+    (0xb1, "Keypad 000"),
+    (0xb2, "Thousands Separator"),
+    (0xb3, "Decimal Separator"),
+    (0xb4, "Currency Unit"),
+    (0xb5, "Currency Sub-unit"),
+    (0xb6, "Keypad ("),
+    (0xb7, "Keypad )"),
+    (0xb8, "Keypad {"),
+    (0xb9, "Keypad }"),
+    (0xba, "Keypad Tab"),
+    (0xbb, "Keypad Backspace"),
+    (0xbc, "Keypad A"),
+    (0xbd, "Keypad B"),
+    (0xbe, "Keypad C"),
+    (0xbf, "Keypad D"),
+    (0xc0, "Keypad E"),
+    (0xc1, "Keypad F"),
+    (0xc2, "Keypad XOR"),
+    (0xc3, "Keypad ^"),
+    (0xc4, "Keypad %"),
+    (0xc5, "Keypad <"),
+    (0xc6, "Keypad >"),
+    (0xc7, "Keypad &"),
+    (0xc8, "Keypad &&"),
+    (0xc9, "Keypad |"),
+    (0xca, "Keypad ||"),
+    (0xcb, "Keypad :"),
+    (0xcc, "Keypad #"),
+    (0xcd, "Keypad Space"),
+    (0xce, "Keypad @"),
+    (0xcf, "Keypad !"),
+    (0xd0, "Keypad Memory Store"),
+    (0xd1, "Keypad Memory Recall"),
+    (0xd2, "Keypad Memory Clear"),
+    (0xd3, "Keypad Memory Add"),
+    (0xd4, "Keypad Memory Subtract"),
+    (0xd5, "Keypad Memory Multiply"),
+    (0xd6, "Keypad Memory Divide"),
+    (0xd7, "Keypad +/-"),
+    (0xd8, "Keypad Clear"),
+    (0xd9, "Keypad Clear Entry"),
+    (0xda, "Keypad Binary"),
+    (0xdb, "Keypad Octal"),
+    (0xdc, "Keypad Decimal"),
+    (0xdd, "Keypad Hexadecimal"),
+    (0xe0, "Left Ctrl"),
+    (0xe1, "Left Shift"),
+    (0xe2, "Left Alt"),
+    (0xe3, "Left Win"),
+    (0xe4, "Right Ctrl"),
+    (0xe5, "Right Shift"),
+    (0xe6, "Right Alt"),
+    (0xe7, "Right Win"),
+];
+
+/// alternative names of keys accepted when parsing, e.g. `Esc`, `PgUp` or the
+/// characters of the US layout punctuation keys
+const KEY_ALIASES: [(&str, u8); 71] = [
+    ("Return", 0x28),
+    ("Esc", 0x29),
+    ("Back Space", 0x2a),
+    ("Spacebar", 0x2c),
+    ("-", 0x2d),
+    ("Hyphen", 0x2d),
+    ("Dash", 0x2d),
+    ("=", 0x2e),
+    ("Equals", 0x2e),
+    ("[", 0x2f),
+    ("]", 0x30),
+    ("\\", 0x31),
+    (";", 0x33),
+    ("'", 0x34),
+    ("Quote", 0x34),
+    ("`", 0x35),
+    ("Backtick", 0x35),
+    ("Grave Accent", 0x35),
+    (",", 0x36),
+    (".", 0x37),
+    ("Dot", 0x37),
+    ("/", 0x38),
+    ("Caps", 0x39),
+    ("PrtSc", 0x46),
+    ("Print", 0x46),
+    ("Break", 0x48),
+    ("Ins", 0x49),
+    ("Del", 0x4c),
+    ("PgUp", 0x4b),
+    ("PgDn", 0x4e),
+    ("Page Dn", 0x4e),
+    ("Right Arrow", 0x4f),
+    ("Arrow Right", 0x4f),
+    ("Left Arrow", 0x50),
+    ("Arrow Left", 0x50),
+    ("Down Arrow", 0x51),
+    ("Arrow Down", 0x51),
+    ("Up Arrow", 0x52),
+    ("Arrow Up", 0x52),
+    ("Keypad Divide", 0x54),
+    ("Keypad Slash", 0x54),
+    ("Keypad Multiply", 0x55),
+    ("Keypad Asterisk", 0x55),
+    ("Keypad Minus", 0x56),
+    ("Keypad Subtract", 0x56),
+    ("Keypad Plus", 0x57),
+    ("Keypad Add", 0x57),
+    ("Keypad Period", 0x63),
+    ("Keypad Decimal Point", 0x63),
+    ("Keypad Equal", 0x67),
+    ("Context Menu", 0x65),
+    ("Apps", 0x65),
+    ("Compose", 0x65),
+    ("Mute Key", 0x7f),
+    ("Left Control", 0xe0),
+    ("LCtrl", 0xe0),
+    ("LShift", 0xe1),
+    ("LAlt", 0xe2),
+    ("Left Super", 0xe3),
+    ("Left Meta", 0xe3),
+    ("Left GUI", 0xe3),
+    ("LWin", 0xe3),
+    ("Right Control", 0xe4),
+    ("RCtrl", 0xe4),
+    ("RShift", 0xe5),
+    ("RAlt", 0xe6),
+    ("AltGr", 0xe6),
+    ("Right Super", 0xe7),
+    ("Right Meta", 0xe7),
+    ("Right GUI", 0xe7),
+    ("RWin", 0xe7),
+];
+
+/// name of the key with HID usage ID `usage`
+pub fn key_name(usage: u8) -> Option<&'static str> {
+    KEY_USAGES
+        .iter()
+        .find(|(code, _)| *code == usage)
+        .map(|(_, name)| *name)
+}
+
+/// HID usage ID of the key named `name` or one of its aliases
+/// compared by `normalize` (`page-up`, `Page Up`), the prefixes `KP`, `Num`
+/// and `Numpad` are accepted for `Keypad`
+pub fn key_usage(name: &str) -> Option<u8> {
+    let name = normalize(name, NAME_SEPARATORS);
+    let keypad = ["numpad", "num", "kp"]
+        .into_iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .map(|key| format!("keypad{key}"));
+    std::iter::once(name.clone())
+        .chain(keypad)
+        .find_map(|name| {
+            KEY_USAGES
+                .iter()
+                .map(|(code, key)| (*key, *code))
+                .chain(KEY_ALIASES)
+                .find(|(key, _)| normalize(key, NAME_SEPARATORS) == name)
+                .map(|(_, code)| code)
+        })
+}
+
+/// Key with modifiers as bound to a button, e.g. `Ctrl+Shift+F5`
+/// parsed from the modifiers and the key name joined by `+`, keys without a
+/// name and unknown modifier bits are written as hex numbers (`0x87`)
+///
+/// * `modifiers`: modifier bits
+/// * `usage`: HID usage ID of the key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub modifiers: Modifiers,
+    pub usage: u8,
+}
+impl KeyCombo {
+    /// Parses `s` like `from_str`, with `layout` the key is the character it
+    /// types on that layout (`Ctrl+Z` is usage 0x1c on de).
+    pub fn parse_in(s: &str, layout: Option<KeyboardLayout>) -> Result<Self, String> {
+        let mut modifiers = Modifiers::NONE;
+        let mut key = s.trim();
+        // leading modifiers, the key itself may contain `+` (`Keypad +`)
+        while let Some((name, rest)) = key.split_once('+') {
+            let Some(modifier) = Modifiers::from_name(name.trim()) else {
+                break;
+            };
+            if rest.trim().is_empty() {
+                break;
+            }
+            modifiers = modifiers | modifier;
+            key = rest.trim();
+        }
+        let usage = match (parse_hex(key), layout) {
+            (Some(usage), _) => Ok(usage),
+            (None, Some(layout)) => layout.key_usage(key),
+            (None, None) => key_usage(key).ok_or_else(|| format!("unknown key `{key}`")),
+        };
+        let message = match usage {
+            Ok(usage) => return Ok(Self { modifiers, usage }),
+            Err(message) => message,
+        };
+        match key.split_once('+') {
+            Some((name, rest))
+                if rest.trim().is_empty() && Modifiers::from_name(name.trim()).is_some() =>
+            {
+                Err(format!("missing key after `{key}`"))
+            }
+            Some((name, _)) if !name.trim().is_empty() => Err(format!(
+                "unknown modifier `{}`, expected Ctrl, Shift, Alt or Win",
+                name.trim()
+            )),
+            _ => Err(message),
+        }
+    }
+
+    /// Formats the combo like `Display`, with `layout` the key is shown as
+    /// the character it types on that layout.
+    pub fn format(&self, layout: Option<KeyboardLayout>) -> String {
+        let mut text = String::new();
+        let mut unknown = self.modifiers.0;
+        for (modifier, name) in Modifiers::NAMES {
+            if self.modifiers.contains(modifier) {
+                text += &format!("{name}+");
+                unknown &= !modifier.0;
+            }
+        }
+        if unknown != 0 {
+            text += &format!("{unknown:#04x}+");
+        }
+        let name = match layout {
+            Some(layout) => layout.key_name(self.usage),
+            None => key_name(self.usage),
+        };
+        match name {
+            Some(name) => text + name,
+            None => text + &format!("{:#04x}", self.usage),
+        }
+    }
+}
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(None))
+    }
+}
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_in(s, None)
+    }
+}
+
+/// parses `0xNN`, raw values allow binding keys and modifiers without a name
+fn parse_hex(input: &str) -> Option<u8> {
+    let hex = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))?;
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Keyboard layouts with built-in key labels, used to bind and show keys by
+/// the character they type instead of their position on a US keyboard.
+/// HID usage IDs name key positions, e.g. usage 0x1c is `Y` on the US layout
+/// but types `Z` on the German layout.
+///
+/// Only the characters typed without modifiers are known, characters needing
+/// Shift or AltGr (e.g. `@` or `/` on de) have no key of their own. Dead keys
+/// (`´` and `^` on de) are labelled with their accent. The digit keys of fr
+/// keep their digits as names, the characters typed without Shift (`&`, `é`,
+/// ...) are accepted as well.
+///
+/// The labels are built-in tables of the plain XKB layouts (and their
+/// `nodeadkeys` variants typing the same characters), the active XKB keymap
+/// itself is not translated. Other variants such as `us(dvorak)` or
+/// `de(neo)` are rejected instead of using the wrong labels.
+/// - Us: US QWERTY
+/// - De: German QWERTZ
+/// - Fr: French AZERTY
+/// - Gb: UK QWERTY
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyboardLayout {
+    Us,
+    De,
+    Fr,
+    Gb,
+}
+impl KeyboardLayout {
+    pub const ALL: [Self; 4] = [Self::Us, Self::De, Self::Fr, Self::Gb];
+
+    /// characters of the US layout as (usage, label)
+    const US_LABELS: [(u8, &'static str); 47] = [
+        (0x04, "A"),
+        (0x05, "B"),
+        (0x06, "C"),
+        (0x07, "D"),
+        (0x08, "E"),
+        (0x09, "F"),
+        (0x0a, "G"),
+        (0x0b, "H"),
+        (0x0c, "I"),
+        (0x0d, "J"),
+        (0x0e, "K"),
+        (0x0f, "L"),
+        (0x10, "M"),
+        (0x11, "N"),
+        (0x12, "O"),
+        (0x13, "P"),
+        (0x14, "Q"),
+        (0x15, "R"),
+        (0x16, "S"),
+        (0x17, "T"),
+        (0x18, "U"),
+        (0x19, "V"),
+        (0x1a, "W"),
+        (0x1b, "X"),
+        (0x1c, "Y"),
+        (0x1d, "Z"),
+        (0x1e, "1"),
+        (0x1f, "2"),
+        (0x20, "3"),
+        (0x21, "4"),
+        (0x22, "5"),
+        (0x23, "6"),
+        (0x24, "7"),
+        (0x25, "8"),
+        (0x26, "9"),
+        (0x27, "0"),
+        (0x2d, "-"),
+        (0x2e, "="),
+        (0x2f, "["),
+        (0x30, "]"),
+        (0x31, "\\"),
+        (0x33, ";"),
+        (0x34, "'"),
+        (0x35, "`"),
+        (0x36, ","),
+        (0x37, "."),
+        (0x38, "/"),
+    ];
+
+    /// labels differing from the US layout, the first label of a usage is shown
+    const DE_LABELS: [(u8, &'static str); 13] = [
+        (0x1c, "Z"),
+        (0x1d, "Y"),
+        (0x2d, "ß"),
+        (0x2e, "´"),
+        (0x2f, "Ü"),
+        (0x30, "+"),
+        // the ISO key, Linux maps the US backslash key to the same key code
+        (0x32, "#"),
+        (0x31, "#"),
+        (0x33, "Ö"),
+        (0x34, "Ä"),
+        (0x35, "^"),
+        (0x38, "-"),
+        (0x64, "<"),
+    ];
+
+    /// labels differing from the US layout, the first label of a usage is shown
+    const FR_LABELS: [(u8, &'static str); 37] = [
+        (0x04, "Q"),
+        (0x14, "A"),
+        (0x1a, "Z"),
+        (0x1d, "W"),
+        (0x10, ","),
+        (0x33, "M"),
+        (0x1e, "1"),
+        (0x1e, "&"),
+        (0x1f, "2"),
+        (0x1f, "é"),
+        (0x20, "3"),
+        (0x20, "\""),
+        (0x21, "4"),
+        (0x21, "'"),
+        (0x22, "5"),
+        (0x22, "("),
+        (0x23, "6"),
+        (0x23, "-"),
+        (0x24, "7"),
+        (0x24, "è"),
+        (0x25, "8"),
+        (0x25, "_"),
+        (0x26, "9"),
+        (0x26, "ç"),
+        (0x27, "0"),
+        (0x27, "à"),
+        (0x2d, ")"),
+        (0x2f, "^"),
+        (0x30, "$"),
+        (0x32, "*"),
+        (0x31, "*"),
+        (0x34, "ù"),
+        (0x35, "²"),
+        (0x36, ";"),
+        (0x37, ":"),
+        (0x38, "!"),
+        (0x64, "<"),
+    ];
+
+    /// labels differing from the US layout, the first label of a usage is shown
+    const GB_LABELS: [(u8, &'static str); 3] = [(0x32, "#"), (0x31, "#"), (0x64, "\\")];
+
+    /// name of the layout as used by XKB
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Us => "us",
+            Self::De => "de",
+            Self::Fr => "fr",
+            Self::Gb => "gb",
+        }
+    }
+
+    /// XKB variants typing the same characters without modifiers as the
+    /// layout itself
+    const fn variants(self) -> &'static [&'static str] {
+        match self {
+            Self::Us | Self::Gb => &["basic"],
+            Self::De | Self::Fr => &["basic", "nodeadkeys"],
+        }
+    }
+
+    /// labels differing from the US layout
+    const fn own_labels(self) -> &'static [(u8, &'static str)] {
+        match self {
+            Self::Us => &[],
+            Self::De => &Self::DE_LABELS,
+            Self::Fr => &Self::FR_LABELS,
+            Self::Gb => &Self::GB_LABELS,
+        }
+    }
+
+    /// all labels of the layout, the own labels first followed by the US
+    /// labels of the keys the layout does not relabel
+    fn labels(self) -> impl Iterator<Item = (u8, &'static str)> {
+        let own = self.own_labels();
+        own.iter().copied().chain(
+            Self::US_LABELS
+                .into_iter()
+                .filter(|(usage, _)| !own.iter().any(|(own, _)| own == usage)),
+        )
+    }
+
+    /// character typed by the key with HID usage ID `usage`
+    pub fn label(self, usage: u8) -> Option<&'static str> {
+        self.labels()
+            .find(|(code, _)| *code == usage)
+            .map(|(_, label)| label)
+    }
+
+    /// name of the key with HID usage ID `usage`, its character if it types one
+    pub fn key_name(self, usage: u8) -> Option<&'static str> {
+        self.label(usage).or_else(|| key_name(usage))
+    }
+
+    /// HID usage ID of the key typing `name` on this layout, case insensitive.
+    /// Key names of the US layout typing a character (`Y`, `Minus`) are
+    /// translated by their character, all other names (`F5`, `Page Up`) are
+    /// positional.
+    pub fn key_usage(self, name: &str) -> Result<u8, String> {
+        let label_usage = |label: &str| {
+            self.labels()
+                .find(|(_, own)| {
+                    normalize(own, NAME_SEPARATORS) == normalize(label, NAME_SEPARATORS)
+                })
+                .map(|(usage, _)| usage)
+        };
+        if let Some(usage) = label_usage(name.trim()) {
+            return Ok(usage);
+        }
+        let usage = key_usage(name).ok_or_else(|| format!("unknown key `{name}`"))?;
+        let Some((_, label)) = Self::US_LABELS.iter().find(|(code, _)| *code == usage) else {
+            return Ok(usage);
+        };
+        label_usage(label)
+            .ok_or_else(|| format!("`{label}` has no key of its own on the {self} layout"))
+    }
+
+    /// Detects the active layout from `XKB_DEFAULT_LAYOUT` and
+    /// `XKB_DEFAULT_VARIANT`, `setxkbmap -query` (X11 and XWayland) or
+    /// `/etc/default/keyboard`, the first of several configured layouts is
+    /// used. Fails if no layout is found or it is not one of the built-in
+    /// layouts and variants.
+    pub fn detect() -> Result<Self, String> {
+        let from_env = || {
+            let layout = std::env::var("XKB_DEFAULT_LAYOUT")
+                .ok()
+                .filter(|layout| !layout.trim().is_empty())?;
+            Some((
+                layout,
+                std::env::var("XKB_DEFAULT_VARIANT").unwrap_or_default(),
+            ))
+        };
+        let from_setxkbmap = || {
+            let output = std::process::Command::new("setxkbmap")
+                .arg("-query")
+                .output()
+                .ok()?;
+            let output = String::from_utf8_lossy(&output.stdout);
+            let value = |key: &str| {
+                output
+                    .lines()
+                    .find_map(|line| line.strip_prefix(key))
+                    .map(|value| value.trim().to_string())
+            };
+            Some((value("layout:")?, value("variant:").unwrap_or_default()))
+        };
+        let from_config = || {
+            let config = std::fs::read_to_string("/etc/default/keyboard").ok()?;
+            let value = |key: &str| {
+                config
+                    .lines()
+                    .find_map(|line| line.trim().strip_prefix(key))
+                    .map(|value| value.trim_matches('"').to_string())
+            };
+            Some((
+                value("XKBLAYOUT=")?,
+                value("XKBVARIANT=").unwrap_or_default(),
+            ))
+        };
+        let (layouts, variants) = from_env()
+            .or_else(from_setxkbmap)
+            .or_else(from_config)
+            .ok_or("no active keyboard layout found, choose one of us, de, fr or gb")?;
+        let first = |list: &str| {
+            list.split(',')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+        match (first(&layouts), first(&variants)) {
+            (layout, variant) if variant.is_empty() => layout.parse(),
+            (layout, variant) => format!("{layout}({variant})").parse(),
+        }
+    }
+}
+impl fmt::Display for KeyboardLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+impl FromStr for KeyboardLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `de(nodeadkeys)` as in XKB or `de:nodeadkeys` as in localectl
+        let (name, variant) = match s.trim().split_once(['(', ':']) {
+            Some((name, variant)) => (name, variant.trim_end_matches(')').trim()),
+            None => (s.trim(), ""),
+        };
+        let layout = match name.to_lowercase().as_str() {
+            "uk" => Self::Gb,
+            name => Self::ALL
+                .into_iter()
+                .find(|layout| layout.name() == name)
+                .ok_or_else(|| {
+                    format!("unsupported keyboard layout `{s}`, expected us, de, fr or gb")
+                })?,
+        };
+        if variant.is_empty() || layout.variants().contains(&variant.to_lowercase().as_str()) {
+            Ok(layout)
+        } else {
+            Err(format!(
+                "unsupported keyboard layout variant `{s}`, only the plain us, de, fr and gb \
+                 layouts have built-in key labels"
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fr_labels_match_xkb() {
+        let fr = KeyboardLayout::Fr;
+        assert_eq!(fr.label(0x38), Some("!"));
+        assert_eq!(fr.label(0x64), Some("<"));
+        assert_eq!(fr.key_usage("!"), Ok(0x38));
+        assert_eq!(fr.key_usage("<"), Ok(0x64));
+        // shifted characters have no key of their own
+        assert!(KeyboardLayout::De.key_usage(">").is_err());
+    }
+
+    #[test]
+    fn variants_without_labels_are_rejected() {
+        for layout in [
+            "us(dvorak)",
+            "us(colemak)",
+            "de(neo)",
+            "us:colemak",
+            "fr(bepo)",
+        ] {
+            let error = layout.parse::<KeyboardLayout>().unwrap_err();
+            assert!(
+                error.starts_with("unsupported keyboard layout variant"),
+                "{error}"
+            );
+        }
+        for (layout, expected) in [
+            ("de", KeyboardLayout::De),
+            ("de(nodeadkeys)", KeyboardLayout::De),
+            ("fr:nodeadkeys", KeyboardLayout::Fr),
+            ("us(basic)", KeyboardLayout::Us),
+            ("uk", KeyboardLayout::Gb),
+        ] {
+            assert_eq!(layout.parse(), Ok(expected), "{layout}");
+        }
+    }
+
+    #[test]
+    fn key_names_follow_one_normalization() {
+        assert_eq!(key_usage("non-us #"), Some(0x32));
+        assert_eq!(key_usage("Keypad -"), Some(0x56));
+        assert_eq!(key_usage("kp-"), Some(0x56));
+        assert_eq!(key_usage("keypad /"), Some(0x54));
+        assert_eq!(key_usage("Keypad +/-"), Some(0xd7));
+        assert_eq!(key_usage("-"), Some(0x2d));
+        assert_eq!(key_usage("keypad"), None);
+    }
+}
@@ -1,4 +1,9 @@
-use crate::profile::profile_attribute_args::{Range, Translation};
+use crate::device_info::FirmwareVersion;
+use crate::error::{Error, Result};
+use crate::profile::button_action::ButtonAction;
+use crate::profile::profile_attribute_args::{FirmwareRange, Range, Translation};
+use crate::profile::settings::CpiStage;
+use crate::profile::{NAME_SEPARATORS, normalize};
 
 pub struct ProfileAttribute {
     pub name: String,
@@ -6,26 +11,78 @@ pub struct ProfileAttribute {
     pub addresses: Vec<u8>,
     pub has_datafield: bool,
     pub datafield_addresses: Option<Vec<u8>>,
+    pub firmware: FirmwareRange,
     pub attribute_handler: Box<dyn ProfileAttributeHandler>,
 }
+impl ProfileAttribute {
+    /// whether the attribute exists in the profile layout of `firmware`,
+    /// an unknown firmware is assumed to support every attribute
+    pub fn supports(&self, firmware: Option<FirmwareVersion>) -> bool {
+        firmware.is_none_or(|version| self.firmware.contains(version))
+    }
+
+    /// fails with `Error::UnsupportedField` if `firmware` does not support the attribute
+    pub fn check_firmware(&self, firmware: Option<FirmwareVersion>) -> Result<()> {
+        match firmware {
+            Some(version) if !self.firmware.contains(version) => Err(Error::UnsupportedField {
+                field: self.name.clone(),
+                firmware: version,
+                supported: self.firmware,
+            }),
+            _ => Ok(()),
+        }
+    }
 
+    /// all profile addresses of the attribute including its data field
+    pub fn all_addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.addresses
+            .iter()
+            .chain(self.datafield_addresses.iter().flatten())
+            .map(|adr| (*adr) as usize)
+    }
+}
+
+/// Decodes and encodes the bytes of a `ProfileAttribute`
+/// - validate: checks the bytes read from the profile
+/// - tostring: decodes the bytes into a human readable value
+/// - fromstring: parses a human readable value into the bytes, the inverse of
+///   `tostring`, failing with a message naming the allowed values
 pub trait ProfileAttributeHandler {
-    fn validate(&self, data: &Vec<u8>) -> bool;
-    fn tostring(&self, data: &Vec<u8>) -> String;
+    fn validate(&self, data: &[u8]) -> bool;
+    fn tostring(&self, data: &[u8]) -> String;
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String>;
+}
+
+/// splits `input` into its leading number and the trimmed remainder,
+/// e.g. `"0.9 mm"` into `(0.9, "mm")`
+fn split_number(input: &str) -> Option<(f32, &str)> {
+    let input = input.trim();
+    let end = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(input.len());
+    let value = input[..end].parse().ok()?;
+    Some((value, input[end..].trim()))
 }
 
 pub struct SwitchAttributeHandler;
 impl ProfileAttributeHandler for SwitchAttributeHandler {
-    fn validate(&self, data: &Vec<u8>) -> bool {
+    fn validate(&self, data: &[u8]) -> bool {
         data[0] == 0 || data[0] == 1
     }
-    fn tostring(&self, data: &Vec<u8>) -> String {
+    fn tostring(&self, data: &[u8]) -> String {
         match data[0] {
             0 => String::from("OFF"),
             1 => String::from("ON"),
             _ => String::from("Not Supported"),
         }
     }
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String> {
+        match normalize(input, NAME_SEPARATORS).as_str() {
+            "off" | "0" | "false" | "no" | "disabled" => Ok(vec![0]),
+            "on" | "1" | "true" | "yes" | "enabled" => Ok(vec![1]),
+            _ => Err(format!("invalid value `{input}`, expected ON or OFF")),
+        }
+    }
 }
 
 pub struct SingleByteContinuousAttribute {
@@ -37,16 +94,53 @@ impl SingleByteContinuousAttribute {
     }
 }
 impl ProfileAttributeHandler for SingleByteContinuousAttribute {
-    fn validate(&self, data: &Vec<u8>) -> bool {
+    fn validate(&self, data: &[u8]) -> bool {
         let at_least_min = data[0] >= self.range.code_min;
         let at_most_max = data[0] <= self.range.code_max;
-        let valid_val = (data[0] - self.range.code_min) % self.range.code_step == 0;
-        at_least_min && at_most_max && valid_val
+        // only subtracting once the value is known to be at least the minimum
+        at_least_min
+            && at_most_max
+            && (data[0] - self.range.code_min).is_multiple_of(self.range.code_step)
+    }
+    fn tostring(&self, data: &[u8]) -> String {
+        format!("{}{}", self.decode(data[0]), self.range.unit)
+    }
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String> {
+        let unit = &self.range.unit;
+        let expected = || {
+            format!(
+                "expected {}{unit} to {}{unit} in steps of {}{unit}",
+                self.decode(self.range.code_min),
+                self.decode(self.range.code_max),
+                self.range.decode_step
+            )
+        };
+        let value = match split_number(input) {
+            Some((value, suffix))
+                if suffix.is_empty() || suffix.eq_ignore_ascii_case(unit.trim()) =>
+            {
+                value
+            }
+            _ => return Err(format!("invalid value `{input}`, {}", expected())),
+        };
+        let steps = (value - self.range.decode_min) / self.range.decode_step;
+        if (steps - steps.round()).abs() > 0.01 {
+            return Err(format!("{input} is not a valid step, {}", expected()));
+        }
+        let code = steps.round() * (self.range.code_step as f32) + (self.range.code_min as f32);
+        if code < self.range.code_min as f32 || code > self.range.code_max as f32 {
+            return Err(format!("{input} is out of range, {}", expected()));
+        }
+        Ok(vec![code as u8])
     }
-    fn tostring(&self, data: &Vec<u8>) -> String {
-        let delta = self.range.decode_min - (self.range.code_min as f32);
-        let val = (data[0] as f32) * self.range.decode_step + delta;
-        format!("{}{}", val.to_string(), self.range.unit)
+}
+impl SingleByteContinuousAttribute {
+    /// value represented by `code`, rounded to the precision of the step
+    fn decode(&self, code: u8) -> f32 {
+        let steps = (code as f32 - self.range.code_min as f32) / self.range.code_step as f32;
+        let val = self.range.decode_min + steps * self.range.decode_step;
+        // removing float artifacts such as 0.90000004
+        (val * 1000.0).round() / 1000.0
     }
 }
 
@@ -59,35 +153,127 @@ impl SingleBinaryAttributeHandler {
     }
 }
 impl ProfileAttributeHandler for SingleBinaryAttributeHandler {
-    fn validate(&self, data: &Vec<u8>) -> bool {
-        // check if is power of 2 (n AND n-1)
-        data[0] > 0 && (data[0] & (data[0] - 1) == 0)
+    fn validate(&self, data: &[u8]) -> bool {
+        // every code is a power of 2, but only the translated ones are meaningful
+        self.translation.code.contains(&data[0])
     }
-    fn tostring(&self, data: &Vec<u8>) -> String {
+    fn tostring(&self, data: &[u8]) -> String {
         match self.translation.code.iter().position(|&x| x == data[0]) {
             Some(index) => self.translation.decode[index].clone(),
             None => "value is not meaningful".into(),
         }
     }
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String> {
+        let wanted = normalize(input, NAME_SEPARATORS);
+        let position = self.translation.decode.iter().position(|decode| {
+            let decode = normalize(decode, NAME_SEPARATORS);
+            if decode == wanted {
+                return true;
+            }
+            // comparing numbers, the unit may be omitted or carry a k prefix
+            match (split_number(&decode), split_number(&wanted)) {
+                (Some((expected, unit)), Some((value, suffix))) => {
+                    let value = match suffix {
+                        "" => value,
+                        s if s == unit => value,
+                        s if s.strip_prefix('k') == Some(unit) => value * 1000.0,
+                        _ => return false,
+                    };
+                    value == expected
+                }
+                _ => false,
+            }
+        });
+        match position {
+            Some(index) => Ok(vec![self.translation.code[index]]),
+            None => Err(format!(
+                "invalid value `{input}`, expected one of {}",
+                self.translation.decode.join(", ")
+            )),
+        }
+    }
+}
+
+/// Signed single byte in two's complement, e.g. 0x81 for -127
+///
+/// * `min`: smallest valid value
+/// * `max`: largest valid value
+/// * `unit`: unit appended to the value
+pub struct SignedSingleByteAttributeHandler {
+    pub min: i8,
+    pub max: i8,
+    pub unit: String,
+}
+impl ProfileAttributeHandler for SignedSingleByteAttributeHandler {
+    fn validate(&self, data: &[u8]) -> bool {
+        (self.min..=self.max).contains(&(data[0] as i8))
+    }
+    fn tostring(&self, data: &[u8]) -> String {
+        if !self.validate(data) {
+            return "value is not meaningful".into();
+        }
+        format!("{}{}", data[0] as i8, self.unit)
+    }
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String> {
+        let unit = self.unit.trim();
+        let expected = || format!("expected {}{unit} to {}{unit}", self.min, self.max);
+        let value = match split_number(input) {
+            Some((value, suffix)) if suffix.is_empty() || suffix.eq_ignore_ascii_case(unit) => {
+                value
+            }
+            _ => return Err(format!("invalid value `{input}`, {}", expected())),
+        };
+        if value.fract() != 0.0 {
+            return Err(format!("{input} is not a whole number, {}", expected()));
+        }
+        if value < f32::from(self.min) || value > f32::from(self.max) {
+            return Err(format!("{input} is out of range, {}", expected()));
+        }
+        Ok(vec![(value as i8) as u8])
+    }
+}
+
+/// Button action of two action bytes followed by the four byte data field,
+/// see `ButtonAction`
+///
+/// * `fixed_cpi`: whether the button may be mapped to `ButtonAction::FixedCpi`
+pub struct ButtonActionHandler {
+    pub fixed_cpi: bool,
+}
+impl ProfileAttributeHandler for ButtonActionHandler {
+    fn validate(&self, data: &[u8]) -> bool {
+        match ButtonAction::decode(data) {
+            ButtonAction::Unknown { .. } => false,
+            ButtonAction::FixedCpi(_) => self.fixed_cpi,
+            _ => true,
+        }
+    }
+    fn tostring(&self, data: &[u8]) -> String {
+        ButtonAction::decode(data).to_string()
+    }
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String> {
+        let action: ButtonAction = input.parse()?;
+        if matches!(action, ButtonAction::FixedCpi(_)) && !self.fixed_cpi {
+            return Err("Fixed CPI is not supported by the scroll wheel".into());
+        }
+        Ok(action.encode().to_vec())
+    }
 }
 
+/// CPI stage with independent X and Y axis, see `CpiStage`
 pub struct DpiRangeHandler;
 impl ProfileAttributeHandler for DpiRangeHandler {
-    fn validate(&self, data: &Vec<u8>) -> bool {
-        let min: u16 = 10;
-        let max: u16 = 30_000;
-        let step: u16 = 10;
-        let left: u16 = (data[0] as u16) + (data[1] as u16) * ((16 as u32).pow(2) as u16);
-        let right: u16 = (data[2] as u16) + (data[3] as u16) * ((16 as u32).pow(2) as u16);
-        let left_valid: bool = left >= min && left <= max && left % step == 0;
-        let right_valid: bool = right >= min && right <= max && right % step == 0;
-
-        left_valid && right_valid
+    fn validate(&self, data: &[u8]) -> bool {
+        CpiStage::from_bytes(data).is_ok()
     }
-    fn tostring(&self, data: &Vec<u8>) -> String {
-        let left: u16 = (data[0] as u16) + (data[1] as u16) * ((16 as u32).pow(2) as u16);
-        let right: u16 = (data[2] as u16) + (data[3] as u16) * ((16 as u32).pow(2) as u16);
-        format!("{left}dpi {right}dpi")
+    fn tostring(&self, data: &[u8]) -> String {
+        match CpiStage::from_bytes(data) {
+            Ok(stage) => stage.to_string(),
+            Err(message) => message,
+        }
+    }
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String> {
+        Ok(input.parse::<CpiStage>()?.to_bytes().to_vec())
     }
 }
 
@@ -112,12 +298,17 @@ impl KailhButtonFilterHandler {
         }
     }
 }
+impl Default for KailhButtonFilterHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl ProfileAttributeHandler for KailhButtonFilterHandler {
-    fn validate(&self, data: &Vec<u8>) -> bool {
-        let in_range: bool = data[0] >= self.range.code_min && data[1] <= self.range.code_max;
+    fn validate(&self, data: &[u8]) -> bool {
+        let in_range: bool = data[0] >= self.range.code_min && data[0] <= self.range.code_max;
         in_range || data[0] == self.speed_mode || data[0] == self.safe_mode
     }
-    fn tostring(&self, data: &Vec<u8>) -> String {
+    fn tostring(&self, data: &[u8]) -> String {
         let in_range = data[0] >= self.range.code_min && data[0] <= self.range.code_max;
         if in_range {
             format!("{}", data[0])
@@ -129,4 +320,198 @@ impl ProfileAttributeHandler for KailhButtonFilterHandler {
             "Invalid Value Found".into()
         }
     }
+    fn fromstring(&self, input: &str) -> std::result::Result<Vec<u8>, String> {
+        let name = normalize(input, NAME_SEPARATORS);
+        let name = name.strip_prefix("gx").unwrap_or(&name);
+        let name = name.strip_suffix("mode").unwrap_or(name);
+        match name {
+            "speed" => return Ok(vec![self.speed_mode]),
+            "safe" => return Ok(vec![self.safe_mode]),
+            _ => {}
+        }
+        match input.trim().parse::<u8>() {
+            Ok(level) if level >= self.range.code_min && level <= self.range.code_max => {
+                Ok(vec![level])
+            }
+            _ => Err(format!(
+                "invalid value `{input}`, expected {} to {}, GX Safe Mode or GX Speed Mode",
+                self.range.code_min, self.range.code_max
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::profile_base::Profile;
+    use crate::profile::profile_fields::{MouseProfile, ProfileFieldName};
+
+    /// all attributes of `MouseProfile` including the Glass Mode LOD
+    fn attributes(fields: &MouseProfile) -> Vec<&ProfileAttribute> {
+        let mut attributes: Vec<&ProfileAttribute> = fields.hashmap().into_values().collect();
+        attributes.push(&fields.glass_mode_liftoff_distance);
+        attributes
+    }
+
+    /// valid encodings of `attribute`: every valid code of single byte
+    /// attributes, samples of the CPI stages and button actions otherwise
+    fn valid_data(attribute: &ProfileAttribute) -> Vec<Vec<u8>> {
+        let candidates: Vec<Vec<u8>> = match attribute.all_addresses().count() {
+            1 => (0..=255).map(|code| vec![code]).collect(),
+            4 => [10, 400, 800, 1600, 30_000]
+                .into_iter()
+                .flat_map(|x: u16| {
+                    [10, 1600, 30_000]
+                        .into_iter()
+                        .map(move |y: u16| [x.to_le_bytes(), y.to_le_bytes()].concat())
+                })
+                .collect(),
+            6 => [
+                "left-click",
+                "right-click",
+                "middle-click",
+                "forward",
+                "back",
+                "scroll-up",
+                "scroll-down",
+                "cpi-loop",
+                "play-pause",
+                "next",
+                "previous",
+                "mute",
+                "volume-up",
+                "volume-down",
+                "browser",
+                "explorer",
+                "disable",
+                "fixed-cpi:800x1600",
+                "key:Ctrl+Shift+F5",
+                "key:0x0f+0x87",
+            ]
+            .into_iter()
+            .map(|action| action.parse::<ButtonAction>().unwrap().encode().to_vec())
+            .collect(),
+            n => panic!("{}: no samples for {n} bytes", attribute.name),
+        };
+        candidates
+            .into_iter()
+            .filter(|data| attribute.attribute_handler.validate(data))
+            .collect()
+    }
+
+    #[test]
+    fn fromstring_inverts_tostring() {
+        let fields = MouseProfile::new();
+        for attribute in attributes(&fields) {
+            let handler = &attribute.attribute_handler;
+            let valid = valid_data(attribute);
+            assert!(!valid.is_empty(), "{}: no valid data", attribute.name);
+            for data in valid {
+                let text = handler.tostring(&data);
+                assert_eq!(
+                    handler.fromstring(&text),
+                    Ok(data.clone()),
+                    "{}: {data:02x?} decoded as `{text}`",
+                    attribute.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn signed_handler_decodes_twos_complement() {
+        let fields = MouseProfile::new();
+        let handler = &fields.sensor_angle_tuning.attribute_handler;
+        for (byte, text) in [(0x00, "0"), (0x7f, "127"), (0x81, "-127"), (0xff, "-1")] {
+            assert!(handler.validate(&[byte]));
+            assert_eq!(handler.tostring(&[byte]), text);
+            assert_eq!(handler.fromstring(text), Ok(vec![byte]));
+        }
+        assert!(!handler.validate(&[0x80]));
+        assert_eq!(handler.tostring(&[0x80]), "value is not meaningful");
+        assert_eq!(handler.fromstring("127"), Ok(vec![0x7f]));
+        assert_eq!(handler.fromstring("-127"), Ok(vec![0x81]));
+        assert!(handler.fromstring("128").is_err());
+        assert!(handler.fromstring("-128").is_err());
+
+        let mut profile = Profile::init();
+        profile.profile_buf[128] = 0x80;
+        assert_eq!(
+            profile
+                .read_field(&ProfileFieldName::SensorAngleTuning)
+                .unwrap(),
+            "value is not meaningful"
+        );
+        assert!(matches!(
+            profile.sensor_angle_tuning(),
+            Err(Error::InvalidProfile(_))
+        ));
+    }
+
+    #[test]
+    fn fromstring_rejects_invalid_values() {
+        let fields = MouseProfile::new();
+        let cases: [(&ProfileAttribute, &str, &str); 9] = [
+            (
+                &fields.mid_btn_mouse_filter,
+                "26",
+                "26 is out of range, expected 0 to 25 in steps of 1",
+            ),
+            (
+                &fields.liftoff_distance,
+                "0.75mm",
+                "0.75mm is not a valid step, expected 0.7mm to 1.7mm in steps of 0.1mm",
+            ),
+            (
+                &fields.sensor_angle_tuning,
+                "-128",
+                "-128 is out of range, expected -127 to 127",
+            ),
+            (
+                &fields.sensor_angle_tuning,
+                "1.5",
+                "1.5 is not a whole number, expected -127 to 127",
+            ),
+            (
+                &fields.angle_snapping,
+                "maybe",
+                "invalid value `maybe`, expected ON or OFF",
+            ),
+            (
+                &fields.left_btn_mouse_filter,
+                "26",
+                "invalid value `26`, expected 0 to 25, GX Safe Mode or GX Speed Mode",
+            ),
+            (
+                &fields.wheel_up_action,
+                "fixed-cpi:800",
+                "Fixed CPI is not supported by the scroll wheel",
+            ),
+            (
+                &fields.forward_btn_action,
+                "key:hyper+c",
+                "unknown modifier `hyper`, expected Ctrl, Shift, Alt or Win",
+            ),
+            (
+                &fields.cpi_prof1,
+                "5",
+                "X/Y axis: invalid resolution `5`, expected 10dpi to 30000dpi in steps of 10dpi",
+            ),
+        ];
+        for (attribute, input, message) in cases {
+            let result = attribute.attribute_handler.fromstring(input);
+            let error = result.expect_err(&format!("{}: accepted `{input}`", attribute.name));
+            assert_eq!(error, message, "{}: `{input}`", attribute.name);
+        }
+        let polling_rate = fields.hashmap()[&ProfileFieldName::PollRate];
+        let error = polling_rate
+            .attribute_handler
+            .fromstring("300Hz")
+            .unwrap_err();
+        assert!(
+            error.starts_with("invalid value `300Hz`, expected one of "),
+            "{error}"
+        );
+    }
 }
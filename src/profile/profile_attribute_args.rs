@@ -1,3 +1,7 @@
+use std::fmt;
+
+use crate::device_info::FirmwareVersion;
+
 #[derive(Clone)]
 pub struct Range {
     pub decode_min: f32,
@@ -12,3 +16,43 @@ pub struct Translation {
     pub code: Vec<u8>,
     pub decode: Vec<String>,
 }
+
+/// Firmware versions a profile attribute is valid for, both bounds inclusive
+///
+/// * `min`: first supporting version, `None` if supported since the first release
+/// * `max`: last supporting version, `None` if supported by all later releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareRange {
+    pub min: Option<FirmwareVersion>,
+    pub max: Option<FirmwareVersion>,
+}
+impl FirmwareRange {
+    /// valid for every firmware version
+    pub const ALL: Self = Self {
+        min: None,
+        max: None,
+    };
+
+    /// valid from `min` on
+    pub const fn since(min: FirmwareVersion) -> Self {
+        Self {
+            min: Some(min),
+            max: None,
+        }
+    }
+
+    /// whether `version` lies within the range
+    pub fn contains(&self, version: FirmwareVersion) -> bool {
+        self.min.is_none_or(|min| version >= min) && self.max.is_none_or(|max| version <= max)
+    }
+}
+impl fmt::Display for FirmwareRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min, self.max) {
+            (None, None) => write!(f, "all firmware versions"),
+            (Some(min), None) => write!(f, "firmware {min} and later"),
+            (None, Some(max)) => write!(f, "firmware up to {max}"),
+            (Some(min), Some(max)) => write!(f, "firmware {min} to {max}"),
+        }
+    }
+}
@@ -1,31 +1,134 @@
-use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 
+use crate::device_info::FirmwareVersion;
+use crate::error::{Error, Result};
 use crate::profile::{
-    profile_attribute::{
-        ProfileAttribute, SingleBinaryAttributeHandler, SingleByteContinuousAttribute,
-        SwitchAttributeHandler,
-    },
-    profile_attribute_args::{Range, Translation},
+    button_action::{ButtonAction, ProgrammableButton},
+    keys::KeyboardLayout,
+    profile_attribute::ProfileAttribute,
     profile_fields::{MouseProfile, ProfileFieldName},
+    settings::PollRate,
 };
 /// Profile object containing the profile buffer and
 /// added functionality such as:
 /// - init: creates empty profile (zero vec)
+/// - load/save: reads/writes the raw profile image from/to a file
+/// - reset: restores the default values of all mapped fields
+/// - read_field: decodes a single field supported by the firmware
+/// - read_effective_field: decodes the value the mouse actually uses, see Glass Mode
+/// - set_field: parses a human readable value into a single field
+///
+/// While Glass Mode is ON the LOD byte selects 1.0mm or 2.0mm and the
+/// configured polling rate is replaced by 1000Hz internally.
 ///
 /// * `profile_buf`: holds the current profile readout, note [0] and [1] are read/write flags
 /// * `profile_fields`: holds the mouseprofile with all the logic
+/// * `firmware`: firmware the profile was read from, `None` if unknown (e.g. loaded from a file)
+/// * `layout`: keyboard layout key bindings are read and set in, `None` for the US key positions
 pub struct Profile {
     pub profile_buf: [u8; Self::PROFILE_SIZE],
     pub profile_fields: MouseProfile,
+    pub firmware: Option<FirmwareVersion>,
+    pub layout: Option<KeyboardLayout>,
 }
 
 impl Profile {
-    const PROFILE_SIZE: usize = 1041;
+    pub const PROFILE_SIZE: usize = 1041;
+
+    /// Default values of the mapped fields as (address, value):
+    /// 1000Hz, all switches OFF, 0.7mm LOD, 4 CPI levels with
+    /// 400/800/1600/3200dpi, all multiclick filters at 0, Glass Mode OFF
+    /// no sensor angle tuning and all buttons mapped to their own function.
+    /// These are the defaults of this tool, not the factory settings of
+    /// Endgame Gear, no capture of a factory-fresh mouse is available.
+    pub const DEFAULTS: [(usize, u8); 67] = [
+        // polling rate 1000Hz
+        (21, 0x08),
+        // slamclick filter, led on liftoff
+        (22, 0x00),
+        (24, 0x00),
+        // lod 0.7mm
+        (25, 0x00),
+        // angle snapping, ripple control, motion sync
+        (26, 0x00),
+        (27, 0x00),
+        (28, 0x00),
+        // number of cpi levels
+        (30, 0x04),
+        // cpi profiles, little endian for both axes
+        (52, 0x90),
+        (53, 0x01),
+        (54, 0x90),
+        (55, 0x01),
+        (57, 0x20),
+        (58, 0x03),
+        (59, 0x20),
+        (60, 0x03),
+        (62, 0x40),
+        (63, 0x06),
+        (64, 0x40),
+        (65, 0x06),
+        (67, 0x80),
+        (68, 0x0c),
+        (69, 0x80),
+        (70, 0x0c),
+        // multiclick filters left, right, middle, forward, back
+        (77, 0x00),
+        (84, 0x00),
+        (91, 0x00),
+        (98, 0x00),
+        (105, 0x00),
+        // glass mode
+        (127, 0x00),
+        // sensor angle tuning 0
+        (128, 0x00),
+        // button actions right, middle, forward, back, wheel up, wheel down
+        (78, 0x00),
+        (79, 0x02),
+        (85, 0x00),
+        (86, 0x04),
+        (92, 0x00),
+        (93, 0x10),
+        (99, 0x00),
+        (100, 0x08),
+        (113, 0x01),
+        (114, 0x01),
+        (120, 0x01),
+        (121, 0xff),
+        // data fields of the button actions, unused by the actions above
+        (80, 0x00),
+        (81, 0x00),
+        (82, 0x00),
+        (83, 0x00),
+        (87, 0x00),
+        (88, 0x00),
+        (89, 0x00),
+        (90, 0x00),
+        (94, 0x00),
+        (95, 0x00),
+        (96, 0x00),
+        (97, 0x00),
+        (101, 0x00),
+        (102, 0x00),
+        (103, 0x00),
+        (104, 0x00),
+        (115, 0x00),
+        (116, 0x00),
+        (117, 0x00),
+        (118, 0x00),
+        (122, 0x00),
+        (123, 0x00),
+        (124, 0x00),
+        (125, 0x00),
+    ];
 
     pub fn init() -> Self {
         Self {
             profile_buf: [20; Self::PROFILE_SIZE],
             profile_fields: MouseProfile::new(),
+            firmware: None,
+            layout: None,
         }
     }
 
@@ -49,29 +152,265 @@ impl Profile {
             PFN::MidBtnMF,
             PFN::ForwardBtnMF,
             PFN::BackBtnMf,
+            PFN::GlassMode,
+            PFN::SensorAngleTuning,
+            PFN::RightBtnAction,
+            PFN::MidBtnAction,
+            PFN::ForwardBtnAction,
+            PFN::BackBtnAction,
+            PFN::WheelUpAction,
+            PFN::WheelDownAction,
         ];
-        let field_map = self.profile_fields.hashmap();
 
         for field in printout {
-            let attribute: &ProfileAttribute = field_map.get(&field).unwrap();
-            let data: Vec<u8> = attribute
-                .addresses
-                .iter()
-                .map(|adr| self.profile_buf[(*adr) as usize])
-                .collect();
-            println!(
-                "{}: {}",
-                attribute.name,
-                attribute.attribute_handler.tostring(&data)
-            );
+            let attribute: &ProfileAttribute = self.attribute(&field);
+            match (self.read_field(&field), self.read_effective_field(&field)) {
+                (Ok(value), Ok(effective)) if value != effective => println!(
+                    "{}: {} configured, {} effective (Glass Mode)",
+                    attribute.name, value, effective
+                ),
+                (Ok(value), _) if field == PFN::LiftoffDistance && self.is_glass_mode() => {
+                    println!("{}: {} (Glass Mode)", attribute.name, value)
+                }
+                (Ok(value), _) => println!("{}: {}", attribute.name, value),
+                (Err(_), _) => println!("{}: not supported by this firmware", attribute.name),
+            }
+        }
+    }
+
+    /// Attribute decoding `field`, the LOD is decoded as 1.0mm/2.0mm choice
+    /// while Glass Mode is ON.
+    pub fn attribute(&self, field: &ProfileFieldName) -> &ProfileAttribute {
+        if *field == ProfileFieldName::LiftoffDistance && self.is_glass_mode() {
+            return &self.profile_fields.glass_mode_liftoff_distance;
         }
+        self.profile_fields.hashmap().get(field).copied().unwrap()
     }
 
-    fn update(&mut self, buf: &mut [u8; Self::PROFILE_SIZE]) {
+    /// whether Glass Mode is supported by the firmware and ON
+    pub fn is_glass_mode(&self) -> bool {
+        let attribute = &self.profile_fields.glass_mode;
+        attribute.supports(self.firmware)
+            && self.profile_buf[attribute.addresses[0] as usize] == 0x01
+    }
+
+    /// Decodes `field`, fails with `Error::UnsupportedField` if the field is
+    /// not part of the profile layout of `firmware`.
+    /// Key bindings are shown in `layout`.
+    pub fn read_field(&self, field: &ProfileFieldName) -> Result<String> {
+        if let Some(button) = ProgrammableButton::from_field(field) {
+            return Ok(self.button_action(button)?.format(self.layout));
+        }
+        let attribute: &ProfileAttribute = self.attribute(field);
+        attribute.check_firmware(self.firmware)?;
+        // data fields follow the addresses
+        let data: Vec<u8> = attribute
+            .all_addresses()
+            .map(|address| self.profile_buf[address])
+            .collect();
+        Ok(attribute.attribute_handler.tostring(&data))
+    }
+
+    /// Decodes the value of `field` the mouse actually uses, which only
+    /// differs from `read_field` for the polling rate while Glass Mode is ON.
+    pub fn read_effective_field(&self, field: &ProfileFieldName) -> Result<String> {
+        if *field == ProfileFieldName::PollRate && self.is_glass_mode() {
+            let attribute: &ProfileAttribute = self.attribute(field);
+            attribute.check_firmware(self.firmware)?;
+            return Ok(attribute
+                .attribute_handler
+                .tostring(&[PollRate::GLASS_MODE.code()]));
+        }
+        self.read_field(field)
+    }
+
+    pub fn update(&mut self, buf: &mut [u8; Self::PROFILE_SIZE]) {
         self.profile_buf.copy_from_slice(buf);
     }
 
+    /// loads a profile from the raw 1041 byte image at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).map_err(|e| Error::file(path, e))?;
+        let mut buf: [u8; Self::PROFILE_SIZE] = data.try_into().map_err(|data: Vec<u8>| {
+            Error::InvalidProfile(format!(
+                "{} has {} bytes, expected {}",
+                path.display(),
+                data.len(),
+                Self::PROFILE_SIZE
+            ))
+        })?;
+        let mut profile = Self::init();
+        profile.update(&mut buf);
+        Ok(profile)
+    }
+
+    /// saves the raw profile image to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.profile_buf).map_err(|e| Error::file(path, e))
+    }
+
+    /// Sets all mapped fields to `DEFAULTS`. Unmapped bytes and fields not
+    /// supported by `firmware` keep their value as their defaults are not known.
+    pub fn reset(&mut self) {
+        let unsupported: Vec<usize> = self
+            .profile_fields
+            .hashmap()
+            .values()
+            .filter(|attribute| !attribute.supports(self.firmware))
+            .flat_map(|attribute| attribute.all_addresses())
+            .collect();
+        for (address, value) in Self::DEFAULTS {
+            if !unsupported.contains(&address) {
+                self.profile_buf[address] = value;
+            }
+        }
+    }
+
+    /// Parses `input` with the handler of `field` and stores the encoded bytes,
+    /// e.g. `"0.9mm"` for `ProfileFieldName::LiftoffDistance`.
+    /// Key bindings are parsed in `layout`.
+    /// Fails with `Error::InvalidValue` or `Error::UnsupportedField`.
+    pub fn set_field(&mut self, field: &ProfileFieldName, input: &str) -> Result<()> {
+        let attribute: &ProfileAttribute = self.attribute(field);
+        attribute.check_firmware(self.firmware)?;
+        if let Some(button) = ProgrammableButton::from_field(field) {
+            let action = ButtonAction::parse_in(input, self.layout).map_err(|message| {
+                Error::InvalidValue {
+                    field: attribute.name.clone(),
+                    message,
+                }
+            })?;
+            return self.set_button_action(button, action);
+        }
+        let data = attribute
+            .attribute_handler
+            .fromstring(input)
+            .map_err(|message| Error::InvalidValue {
+                field: attribute.name.clone(),
+                message,
+            })?;
+        let addresses: Vec<usize> = attribute.all_addresses().collect();
+        for (address, value) in addresses.into_iter().zip(data) {
+            self.profile_buf[address] = value;
+        }
+        self.coerce_glass_mode_lod();
+        Ok(())
+    }
+
+    /// Resets the LOD to 1.0mm if Glass Mode is ON and the LOD byte is no
+    /// valid Glass Mode value, e.g. right after switching Glass Mode ON.
+    pub(crate) fn coerce_glass_mode_lod(&mut self) {
+        let attribute = &self.profile_fields.glass_mode_liftoff_distance;
+        let address = attribute.addresses[0] as usize;
+        if self.is_glass_mode()
+            && !attribute
+                .attribute_handler
+                .validate(&[self.profile_buf[address]])
+        {
+            self.profile_buf[address] = 0x00;
+        }
+    }
+
     pub fn dump_hex(&self) {
         println!("{:02X?}", self.profile_buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::settings::Lod;
+
+    /// profile with the defaults and Glass Mode switched `on`
+    fn profile(glass_mode: bool) -> Profile {
+        let mut profile = Profile::init();
+        profile.reset();
+        profile.set_glass_mode(glass_mode).unwrap();
+        profile
+    }
+
+    #[test]
+    fn glass_mode_lod_is_decoded_as_choice() {
+        let mut profile = profile(true);
+        for (code, text, lod) in [
+            (0x00, "1mm", Lod::GLASS_MODE_LOW),
+            (0x01, "2mm", Lod::GLASS_MODE_HIGH),
+        ] {
+            profile.profile_buf[25] = code;
+            assert_eq!(
+                profile
+                    .read_field(&ProfileFieldName::LiftoffDistance)
+                    .unwrap(),
+                text
+            );
+            assert_eq!(profile.lift_off_distance().unwrap(), lod);
+        }
+    }
+
+    #[test]
+    fn lod_outside_glass_mode_choice_is_rejected() {
+        let mut profile = profile(true);
+        assert!(matches!(
+            profile.set_field(&ProfileFieldName::LiftoffDistance, "0.9mm"),
+            Err(Error::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            profile.set_lift_off_distance(Lod::from_tenths(9).unwrap()),
+            Err(Error::InvalidValue { .. })
+        ));
+        assert_eq!(profile.profile_buf[25], 0x00);
+
+        let mut profile = self::profile(false);
+        profile
+            .set_field(&ProfileFieldName::LiftoffDistance, "0.9mm")
+            .unwrap();
+        assert_eq!(profile.profile_buf[25], 0x02);
+    }
+
+    #[test]
+    fn switching_glass_mode_on_coerces_lod() {
+        let mut profile = profile(false);
+        profile
+            .set_lift_off_distance(Lod::from_tenths(12).unwrap())
+            .unwrap();
+        profile.set_glass_mode(true).unwrap();
+        assert_eq!(profile.profile_buf[25], 0x00);
+        assert_eq!(profile.lift_off_distance().unwrap(), Lod::GLASS_MODE_LOW);
+
+        // the same through the string interface
+        let mut profile = self::profile(false);
+        profile
+            .set_field(&ProfileFieldName::LiftoffDistance, "1.2mm")
+            .unwrap();
+        profile
+            .set_field(&ProfileFieldName::GlassMode, "ON")
+            .unwrap();
+        assert_eq!(
+            profile
+                .read_field(&ProfileFieldName::LiftoffDistance)
+                .unwrap(),
+            "1mm"
+        );
+    }
+
+    #[test]
+    fn glass_mode_polls_at_1000hz() {
+        let mut profile = profile(true);
+        profile.set_poll_rate(PollRate::Hz8000).unwrap();
+        assert_eq!(profile.poll_rate().unwrap(), PollRate::Hz8000);
+        assert_eq!(profile.effective_poll_rate().unwrap(), PollRate::Hz1000);
+        assert_eq!(
+            profile.read_field(&ProfileFieldName::PollRate).unwrap(),
+            "8000Hz"
+        );
+        assert_eq!(
+            profile
+                .read_effective_field(&ProfileFieldName::PollRate)
+                .unwrap(),
+            "1000Hz"
+        );
+
+        profile.set_glass_mode(false).unwrap();
+        assert_eq!(profile.effective_poll_rate().unwrap(), PollRate::Hz8000);
+    }
+}
@@ -1,6 +1,11 @@
 use std::collections::HashMap;
 
-use crate::profile::{profile_attribute::{DpiRangeHandler, KailhButtonFilterHandler, ProfileAttribute, SingleBinaryAttributeHandler, SingleByteContinuousAttribute, SwitchAttributeHandler}, profile_attribute_args::{Range, Translation}, profile_base::Profile};
+use crate::device_info::FirmwareVersion;
+use crate::profile::{profile_attribute::{ButtonActionHandler, DpiRangeHandler, KailhButtonFilterHandler, ProfileAttribute, SignedSingleByteAttributeHandler, SingleBinaryAttributeHandler, SingleByteContinuousAttribute, SwitchAttributeHandler}, profile_attribute_args::{FirmwareRange, Range, Translation}};
+
+/// firmware Glass Mode and Sensor Angle Tuning were sniffed on, older
+/// firmware may not have these fields
+pub const SNIFFED_FIRMWARE: FirmwareVersion = FirmwareVersion::new(2, 1, 0);
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub enum ProfileFieldName {
@@ -21,6 +26,14 @@ pub enum ProfileFieldName {
     MidBtnMF,
     ForwardBtnMF,
     BackBtnMf,
+    GlassMode,
+    SensorAngleTuning,
+    RightBtnAction,
+    MidBtnAction,
+    ForwardBtnAction,
+    BackBtnAction,
+    WheelUpAction,
+    WheelDownAction,
 }
 pub struct MouseProfile{
     pub poll_rate: ProfileAttribute,
@@ -40,6 +53,15 @@ pub struct MouseProfile{
     pub mid_btn_mouse_filter: ProfileAttribute,
     pub forward_btn_mouse_filter: ProfileAttribute,
     pub back_btn_mouse_filter: ProfileAttribute,
+    pub glass_mode: ProfileAttribute,
+    pub sensor_angle_tuning: ProfileAttribute,
+    pub right_btn_action: ProfileAttribute,
+    pub mid_btn_action: ProfileAttribute,
+    pub forward_btn_action: ProfileAttribute,
+    pub back_btn_action: ProfileAttribute,
+    pub wheel_up_action: ProfileAttribute,
+    pub wheel_down_action: ProfileAttribute,
+    pub glass_mode_liftoff_distance: ProfileAttribute,
 }
 impl MouseProfile{
 
@@ -61,6 +83,7 @@ impl MouseProfile{
                 addresses: vec![21],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SingleBinaryAttributeHandler{
                     translation: Translation{
                         code: vec![0x08, 0x04, 0x02, 0x01],
@@ -75,6 +98,7 @@ impl MouseProfile{
                 addresses: vec![22],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SwitchAttributeHandler),
             },
             disable_led_on_liftoff: ProfileAttribute {
@@ -84,6 +108,7 @@ impl MouseProfile{
                 addresses: vec![24],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SwitchAttributeHandler),
             },
             liftoff_distance:  ProfileAttribute {
@@ -93,6 +118,7 @@ impl MouseProfile{
                 addresses: vec![25],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SingleByteContinuousAttribute {
                     range: Range {
                         decode_min: 0.7,
@@ -111,6 +137,7 @@ impl MouseProfile{
                 addresses: vec![26],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SwitchAttributeHandler),
             },
             ripple_control: ProfileAttribute{
@@ -119,6 +146,7 @@ impl MouseProfile{
                 addresses: vec![27],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SwitchAttributeHandler),
             },
             motion_sync: ProfileAttribute{
@@ -127,6 +155,7 @@ impl MouseProfile{
                 addresses: vec![28],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SwitchAttributeHandler),
             },
             cpi_levels: ProfileAttribute{
@@ -135,6 +164,7 @@ impl MouseProfile{
                 addresses: vec![30],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SingleByteContinuousAttribute{
                     range: Range{
                         code_min: 1,
@@ -148,50 +178,56 @@ impl MouseProfile{
             },
             cpi_prof1: ProfileAttribute{
                 name: "DPI Setting for Profile 1".into(),
-                description: "Sets the DPI for profile 1.\nAllowed values are [10-30.000] in steps of 10.".into(),
+                description: "Sets the DPI of the X and Y axis for profile 1, e.g. 1600 for both axes or 800x1600.\nAllowed values per axis are [10-30.000] in steps of 10.".into(),
                 addresses: vec![52,53,54,55],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(DpiRangeHandler),
             },          
             cpi_prof2: ProfileAttribute{
                 name: "DPI Setting for Profile 2".into(),
-                description: "Sets the DPI for profile 2.\nAllowed values are [10-30.000] in steps of 10.".into(),
+                description: "Sets the DPI of the X and Y axis for profile 2, e.g. 1600 for both axes or 800x1600.\nAllowed values per axis are [10-30.000] in steps of 10.".into(),
                 addresses: vec![57,58,59,60],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(DpiRangeHandler),
             },          
             cpi_prof3: ProfileAttribute{
                 name: "DPI Setting for Profile 3".into(),
-                description: "Sets the DPI for profile 3.\nAllowed values are [10-30.000] in steps of 10.".into(),
+                description: "Sets the DPI of the X and Y axis for profile 3, e.g. 1600 for both axes or 800x1600.\nAllowed values per axis are [10-30.000] in steps of 10.".into(),
                 addresses: vec![62,63,64,65],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(DpiRangeHandler),
             },          
             cpi_prof4: ProfileAttribute{
                 name: "DPI Setting for Profile 4".into(),
-                description: "Sets the DPI for profile 4.\nAllowed values are [10-30.000] in steps of 10.".into(),
+                description: "Sets the DPI of the X and Y axis for profile 4, e.g. 1600 for both axes or 800x1600.\nAllowed values per axis are [10-30.000] in steps of 10.".into(),
                 addresses: vec![67,68,69,70],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(DpiRangeHandler),
             },
             right_btn_mouse_filter: ProfileAttribute{
                 name: "Multiclick Filter Right Mouse Button".into(),
                 description: "Sets the level of filtering multiclicks. The front buttons support special modes GX Speed Mode and GX Safe Mode. GX Speed Mode registers at loss of contact for two contactpoints. GX Safe Mode registers at loss of one of those contactpoints as click. GX Safe Mode is good for worn down switches and is considered a hardware implemented double-click filter.\nAllowed values are [0-25], 240 or 241 which are the filter level, GX Safe Mode or GX Speed Mode respectively.".into(),
-                addresses: vec![77],
+                addresses: vec![84],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(KailhButtonFilterHandler::new()),
             },
             left_btn_mouse_filter: ProfileAttribute{
                 name: "Multiclick Filter Left Mouse Button".into(),
                 description: "Sets the level of filtering multiclicks. The front buttons support special modes GX Speed Mode and GX Safe Mode. GX Speed Mode registers at loss of contact for two contactpoints. GX Safe Mode registers at loss of one of those contactpoints as click. GX Safe Mode is good for worn down switches and is considered a hardware implemented double-click filter.\nAllowed values are [0-25], 240 or 241 which are the filter level, GX Safe Mode or GX Speed Mode respectively.".into(),
-                addresses: vec![84],
+                addresses: vec![77],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(KailhButtonFilterHandler::new())
             },
             mid_btn_mouse_filter: ProfileAttribute{
@@ -200,6 +236,7 @@ impl MouseProfile{
                 addresses: vec![91],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SingleByteContinuousAttribute::new(range_click_filter.clone())),
             },
             forward_btn_mouse_filter: ProfileAttribute{
@@ -208,6 +245,7 @@ impl MouseProfile{
                 addresses: vec![98],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SingleByteContinuousAttribute::new(range_click_filter.clone())),
             },
             back_btn_mouse_filter: ProfileAttribute{
@@ -216,9 +254,104 @@ impl MouseProfile{
                 addresses: vec![105],
                 has_datafield: false,
                 datafield_addresses: None,
+                firmware: FirmwareRange::ALL,
                 attribute_handler: Box::new(SingleByteContinuousAttribute::new(range_click_filter.clone())),
             },
-
+            glass_mode: ProfileAttribute{
+                name: "Glass Mode".into(),
+                description: "Adjusts the sensor for glass surfaces. While enabled the LOD can only be 1.0mm or 2.0mm and the polling rate is fixed to 1000Hz internally.\nAllowed values are [0,1] which represent [OFF, ON] respectively.".into(),
+                addresses: vec![127],
+                has_datafield: false,
+                datafield_addresses: None,
+                firmware: FirmwareRange::since(SNIFFED_FIRMWARE),
+                attribute_handler: Box::new(SwitchAttributeHandler),
+            },
+            sensor_angle_tuning: ProfileAttribute{
+                name: "Sensor Angle Tuning".into(),
+                description: "Rotates the sensor readings to compensate for the angle the mouse is held at.\nAllowed values are [-127 - 127], stored in two's complement.".into(),
+                addresses: vec![128],
+                has_datafield: false,
+                datafield_addresses: None,
+                firmware: FirmwareRange::since(SNIFFED_FIRMWARE),
+                attribute_handler: Box::new(SignedSingleByteAttributeHandler{
+                    min: -127,
+                    max: 127,
+                    unit: "".into(),
+                }),
+            },
+            right_btn_action: ProfileAttribute{
+                name: "Right Mouse Button".into(),
+                description: "Action of the right mouse button, see `ButtonAction`.".into(),
+                addresses: vec![78, 79],
+                has_datafield: true,
+                datafield_addresses: Some(vec![80, 81, 82, 83]),
+                firmware: FirmwareRange::ALL,
+                attribute_handler: Box::new(ButtonActionHandler{ fixed_cpi: true }),
+            },
+            mid_btn_action: ProfileAttribute{
+                name: "Middle Mouse Button".into(),
+                description: "Action of the middle mouse button, see `ButtonAction`.".into(),
+                addresses: vec![85, 86],
+                has_datafield: true,
+                datafield_addresses: Some(vec![87, 88, 89, 90]),
+                firmware: FirmwareRange::ALL,
+                attribute_handler: Box::new(ButtonActionHandler{ fixed_cpi: true }),
+            },
+            forward_btn_action: ProfileAttribute{
+                name: "Forward Mouse Button".into(),
+                description: "Action of the forward mouse button, see `ButtonAction`.".into(),
+                addresses: vec![92, 93],
+                has_datafield: true,
+                datafield_addresses: Some(vec![94, 95, 96, 97]),
+                firmware: FirmwareRange::ALL,
+                attribute_handler: Box::new(ButtonActionHandler{ fixed_cpi: true }),
+            },
+            back_btn_action: ProfileAttribute{
+                name: "Back Mouse Button".into(),
+                description: "Action of the back mouse button, see `ButtonAction`.".into(),
+                addresses: vec![99, 100],
+                has_datafield: true,
+                datafield_addresses: Some(vec![101, 102, 103, 104]),
+                firmware: FirmwareRange::ALL,
+                attribute_handler: Box::new(ButtonActionHandler{ fixed_cpi: true }),
+            },
+            wheel_up_action: ProfileAttribute{
+                name: "Wheel Up".into(),
+                description: "Action of scrolling the wheel up, see `ButtonAction`. Fixed CPI is not supported.".into(),
+                addresses: vec![113, 114],
+                has_datafield: true,
+                datafield_addresses: Some(vec![115, 116, 117, 118]),
+                firmware: FirmwareRange::ALL,
+                attribute_handler: Box::new(ButtonActionHandler{ fixed_cpi: false }),
+            },
+            wheel_down_action: ProfileAttribute{
+                name: "Wheel Down".into(),
+                description: "Action of scrolling the wheel down, see `ButtonAction`. Fixed CPI is not supported.".into(),
+                addresses: vec![120, 121],
+                has_datafield: true,
+                datafield_addresses: Some(vec![122, 123, 124, 125]),
+                firmware: FirmwareRange::ALL,
+                attribute_handler: Box::new(ButtonActionHandler{ fixed_cpi: false }),
+            },
+            // alternative decoding of the LOD byte while Glass Mode is ON, not part of `hashmap`
+            glass_mode_liftoff_distance: ProfileAttribute{
+                name: "LOD (Lift-Off Distance)".into(),
+                description: "Describes at which distance a Lift-Off is considered to be one while Glass Mode is ON.\nAllowed values are [0,1] which represent [1.0mm, 2.0mm] respectively.".into(),
+                addresses: vec![25],
+                has_datafield: false,
+                datafield_addresses: None,
+                firmware: FirmwareRange::since(SNIFFED_FIRMWARE),
+                attribute_handler: Box::new(SingleByteContinuousAttribute {
+                    range: Range {
+                        decode_min: 1.0,
+                        decode_step: 1.0,
+                        code_min: 0x00,
+                        code_step: 0x01,
+                        code_max: 0x01,
+                        unit: "mm".into(),
+                    },
+                }),
+            },
         }
     }
     pub fn hashmap(&self) -> HashMap<ProfileFieldName, &ProfileAttribute>{
@@ -241,6 +374,19 @@ impl MouseProfile{
             (PFN::MidBtnMF, &self.mid_btn_mouse_filter),
             (PFN::ForwardBtnMF, &self.forward_btn_mouse_filter),
             (PFN::BackBtnMf, &self.back_btn_mouse_filter),
+            (PFN::GlassMode, &self.glass_mode),
+            (PFN::SensorAngleTuning, &self.sensor_angle_tuning),
+            (PFN::RightBtnAction, &self.right_btn_action),
+            (PFN::MidBtnAction, &self.mid_btn_action),
+            (PFN::ForwardBtnAction, &self.forward_btn_action),
+            (PFN::BackBtnAction, &self.back_btn_action),
+            (PFN::WheelUpAction, &self.wheel_up_action),
+            (PFN::WheelDownAction, &self.wheel_down_action),
         ])
     }
 }
+impl Default for MouseProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,563 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::profile::{
+    button_action::{ButtonAction, ProgrammableButton},
+    profile_attribute::ProfileAttribute,
+    profile_base::Profile,
+    profile_fields::MouseProfile,
+};
+
+/// Polling rate of the mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PollRate {
+    Hz1000,
+    Hz2000,
+    Hz4000,
+    Hz8000,
+}
+impl PollRate {
+    /// rate used internally while Glass Mode is ON
+    pub const GLASS_MODE: Self = Self::Hz1000;
+
+    /// rate in Hz
+    pub const fn hz(self) -> u32 {
+        match self {
+            Self::Hz1000 => 1000,
+            Self::Hz2000 => 2000,
+            Self::Hz4000 => 4000,
+            Self::Hz8000 => 8000,
+        }
+    }
+
+    pub(crate) const fn code(self) -> u8 {
+        match self {
+            Self::Hz1000 => 0x08,
+            Self::Hz2000 => 0x04,
+            Self::Hz4000 => 0x02,
+            Self::Hz8000 => 0x01,
+        }
+    }
+
+    const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x08 => Some(Self::Hz1000),
+            0x04 => Some(Self::Hz2000),
+            0x02 => Some(Self::Hz4000),
+            0x01 => Some(Self::Hz8000),
+            _ => None,
+        }
+    }
+}
+
+/// Lift-off distance in tenths of a millimeter, 0.7mm to 1.7mm or
+/// 1.0mm and 2.0mm while Glass Mode is ON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lod(u8);
+impl Lod {
+    pub const MIN: Self = Self(7);
+    pub const MAX: Self = Self(17);
+    pub const GLASS_MODE_LOW: Self = Self(10);
+    pub const GLASS_MODE_HIGH: Self = Self(20);
+
+    /// lift-off distance of `tenths` tenths of a millimeter, `None` if valid
+    /// neither with nor without Glass Mode
+    pub const fn from_tenths(tenths: u8) -> Option<Self> {
+        if (tenths >= Self::MIN.0 && tenths <= Self::MAX.0) || tenths == Self::GLASS_MODE_HIGH.0 {
+            Some(Self(tenths))
+        } else {
+            None
+        }
+    }
+
+    /// distance in tenths of a millimeter
+    pub const fn tenths(self) -> u8 {
+        self.0
+    }
+
+    /// distance in millimeters
+    pub fn millimeters(self) -> f32 {
+        f32::from(self.0) / 10.0
+    }
+}
+
+/// Resolution of a single axis, 10 to 30000 in steps of 10
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dpi(u16);
+impl Dpi {
+    pub const MIN: Self = Self(10);
+    pub const MAX: Self = Self(30_000);
+    pub const STEP: u16 = 10;
+
+    /// resolution of `dpi`, `None` if out of range or not a multiple of `STEP`
+    pub const fn new(dpi: u16) -> Option<Self> {
+        if dpi >= Self::MIN.0 && dpi <= Self::MAX.0 && dpi.is_multiple_of(Self::STEP) {
+            Some(Self(dpi))
+        } else {
+            None
+        }
+    }
+
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+}
+
+/// Resolution of one of the four CPI stages, X and Y axis are set
+/// independently, a stage with equal axes is called linked
+/// parsed from and displayed as `1600dpi` (linked) or `800x1600dpi` (X x Y),
+/// the unit is optional and may also be `cpi`
+///
+/// * `x`: horizontal resolution
+/// * `y`: vertical resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CpiStage {
+    pub x: Dpi,
+    pub y: Dpi,
+}
+impl CpiStage {
+    /// linked stage with the same resolution on both axes
+    pub const fn linked(dpi: Dpi) -> Self {
+        Self { x: dpi, y: dpi }
+    }
+
+    /// whether both axes have the same resolution
+    pub fn is_linked(&self) -> bool {
+        self.x == self.y
+    }
+
+    /// Decodes the four bytes of a stage, X and Y little endian.
+    /// Fails with the value of the first invalid axis.
+    pub(crate) fn from_bytes(data: &[u8]) -> std::result::Result<Self, String> {
+        let axis = |name: &str, low: u8, high: u8| {
+            let dpi = u16::from_le_bytes([low, high]);
+            Dpi::new(dpi).ok_or_else(|| format!("{name} axis: invalid resolution {dpi}dpi"))
+        };
+        Ok(Self {
+            x: axis("X", data[0], data[1])?,
+            y: axis("Y", data[2], data[3])?,
+        })
+    }
+
+    /// encodes the stage into four bytes, X and Y little endian
+    pub(crate) fn to_bytes(self) -> [u8; 4] {
+        let [x_low, x_high] = self.x.0.to_le_bytes();
+        let [y_low, y_high] = self.y.0.to_le_bytes();
+        [x_low, x_high, y_low, y_high]
+    }
+}
+impl fmt::Display for CpiStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_linked() {
+            write!(f, "{}dpi", self.x.0)
+        } else {
+            write!(f, "{}x{}dpi", self.x.0, self.y.0)
+        }
+    }
+}
+impl FromStr for CpiStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // units may follow every value, axes are separated by `x` or whitespace
+        let values = s
+            .to_lowercase()
+            .replace("dpi", " ")
+            .replace("cpi", " ")
+            .replace('x', " ");
+        let values: Vec<&str> = values.split_whitespace().collect();
+        let axis = |name: &str, value: &str| {
+            value.parse().ok().and_then(Dpi::new).ok_or_else(|| {
+                format!(
+                    "{name} axis: invalid resolution `{value}`, expected {}dpi to {}dpi in steps of {}dpi",
+                    Dpi::MIN.0,
+                    Dpi::MAX.0,
+                    Dpi::STEP
+                )
+            })
+        };
+        match values[..] {
+            [both] => Ok(Self::linked(axis("X/Y", both)?)),
+            [x, y] => Ok(Self {
+                x: axis("X", x)?,
+                y: axis("Y", y)?,
+            }),
+            _ => Err(format!(
+                "invalid resolution `{s}`, expected DPI for both axes or XxY, e.g. 1600 or 800x1600"
+            )),
+        }
+    }
+}
+
+/// Sensor angle tuning, -127 to 127
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AngleTuning(i8);
+impl AngleTuning {
+    pub const MIN: Self = Self(-127);
+    pub const NONE: Self = Self(0);
+    pub const MAX: Self = Self(127);
+
+    /// angle tuning `value`, `None` if out of range
+    pub const fn new(value: i8) -> Option<Self> {
+        if value >= Self::MIN.0 {
+            Some(Self(value))
+        } else {
+            None
+        }
+    }
+
+    pub const fn get(self) -> i8 {
+        self.0
+    }
+}
+
+/// Buttons with a multiclick filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+    Forward,
+    Back,
+}
+impl Button {
+    /// whether the button supports GX Safe Mode and GX Speed Mode
+    pub const fn supports_gx_modes(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+
+    /// multiclick filter attribute of the button
+    fn filter_attribute(self, fields: &MouseProfile) -> &ProfileAttribute {
+        match self {
+            Self::Left => &fields.left_btn_mouse_filter,
+            Self::Right => &fields.right_btn_mouse_filter,
+            Self::Middle => &fields.mid_btn_mouse_filter,
+            Self::Forward => &fields.forward_btn_mouse_filter,
+            Self::Back => &fields.back_btn_mouse_filter,
+        }
+    }
+}
+
+/// Multiclick filter level, 0 (off) to 25
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FilterLevel(u8);
+impl FilterLevel {
+    pub const OFF: Self = Self(0);
+    pub const MAX: Self = Self(25);
+
+    /// filter level `level`, `None` if out of range
+    pub const fn new(level: u8) -> Option<Self> {
+        if level <= Self::MAX.0 {
+            Some(Self(level))
+        } else {
+            None
+        }
+    }
+
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+/// Multiclick filter of a button, the GX modes are only supported by the
+/// left and right button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClickFilter {
+    Level(FilterLevel),
+    GxSafeMode,
+    GxSpeedMode,
+}
+impl ClickFilter {
+    const GX_SAFE_MODE: u8 = 0xf0;
+    const GX_SPEED_MODE: u8 = 0xf1;
+
+    const fn code(self) -> u8 {
+        match self {
+            Self::Level(level) => level.0,
+            Self::GxSafeMode => Self::GX_SAFE_MODE,
+            Self::GxSpeedMode => Self::GX_SPEED_MODE,
+        }
+    }
+
+    const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            Self::GX_SAFE_MODE => Some(Self::GxSafeMode),
+            Self::GX_SPEED_MODE => Some(Self::GxSpeedMode),
+            level => match FilterLevel::new(level) {
+                Some(level) => Some(Self::Level(level)),
+                None => None,
+            },
+        }
+    }
+}
+
+/// Typed access to the settings of a `Profile`
+/// every getter fails with `Error::InvalidProfile` if the stored bytes are
+/// not a valid value, getters and setters fail with `Error::UnsupportedField`
+/// if the firmware of the profile does not support the field
+impl Profile {
+    /// configured polling rate, see `effective_poll_rate`
+    pub fn poll_rate(&self) -> Result<PollRate> {
+        let code = self.byte(|fields| &fields.poll_rate)?;
+        PollRate::from_code(code).ok_or_else(|| self.invalid(|fields| &fields.poll_rate, code))
+    }
+
+    /// polling rate the mouse uses, fixed to 1000Hz while Glass Mode is ON
+    pub fn effective_poll_rate(&self) -> Result<PollRate> {
+        let configured = self.poll_rate()?;
+        Ok(if self.is_glass_mode() {
+            PollRate::GLASS_MODE
+        } else {
+            configured
+        })
+    }
+
+    pub fn set_poll_rate(&mut self, rate: PollRate) -> Result<()> {
+        self.set_bytes(|fields| &fields.poll_rate, &[rate.code()])
+    }
+
+    pub fn slamclick_filter(&self) -> Result<bool> {
+        self.switch(|fields| &fields.slamclick_filter)
+    }
+
+    pub fn set_slamclick_filter(&mut self, on: bool) -> Result<()> {
+        self.set_bytes(|fields| &fields.slamclick_filter, &[on.into()])
+    }
+
+    pub fn disable_led_on_lift_off(&self) -> Result<bool> {
+        self.switch(|fields| &fields.disable_led_on_liftoff)
+    }
+
+    pub fn set_disable_led_on_lift_off(&mut self, on: bool) -> Result<()> {
+        self.set_bytes(|fields| &fields.disable_led_on_liftoff, &[on.into()])
+    }
+
+    /// lift-off distance, decoded as 1.0mm/2.0mm choice while Glass Mode is ON
+    pub fn lift_off_distance(&self) -> Result<Lod> {
+        let code = self.byte(|fields| &fields.liftoff_distance)?;
+        let lod = match (self.is_glass_mode(), code) {
+            (true, 0x00) => Some(Lod::GLASS_MODE_LOW),
+            (true, 0x01) => Some(Lod::GLASS_MODE_HIGH),
+            (true, _) => None,
+            (false, code) => code
+                .checked_add(Lod::MIN.0)
+                .filter(|tenths| *tenths <= Lod::MAX.0)
+                .map(Lod),
+        };
+        lod.ok_or_else(|| self.invalid(|fields| &fields.liftoff_distance, code))
+    }
+
+    /// Sets the lift-off distance, fails with `Error::InvalidValue` if `lod`
+    /// is not valid in the current Glass Mode.
+    pub fn set_lift_off_distance(&mut self, lod: Lod) -> Result<()> {
+        let code = match (self.is_glass_mode(), lod) {
+            (true, Lod::GLASS_MODE_LOW) => Some(0x00),
+            (true, Lod::GLASS_MODE_HIGH) => Some(0x01),
+            (true, _) => None,
+            (false, lod) if lod <= Lod::MAX => Some(lod.0 - Lod::MIN.0),
+            (false, _) => None,
+        };
+        let Some(code) = code else {
+            let expected = if self.is_glass_mode() {
+                "1.0mm or 2.0mm while Glass Mode is ON"
+            } else {
+                "0.7mm to 1.7mm"
+            };
+            return Err(Error::InvalidValue {
+                field: self.profile_fields.liftoff_distance.name.clone(),
+                message: format!(
+                    "{}mm is out of range, expected {expected}",
+                    lod.millimeters()
+                ),
+            });
+        };
+        self.set_bytes(|fields| &fields.liftoff_distance, &[code])
+    }
+
+    pub fn sensor_angle_tuning(&self) -> Result<AngleTuning> {
+        let code = self.byte(|fields| &fields.sensor_angle_tuning)?;
+        AngleTuning::new(code as i8)
+            .ok_or_else(|| self.invalid(|fields| &fields.sensor_angle_tuning, code))
+    }
+
+    pub fn set_sensor_angle_tuning(&mut self, tuning: AngleTuning) -> Result<()> {
+        self.set_bytes(|fields| &fields.sensor_angle_tuning, &[tuning.0 as u8])
+    }
+
+    pub fn glass_mode(&self) -> Result<bool> {
+        self.switch(|fields| &fields.glass_mode)
+    }
+
+    /// switches Glass Mode, the LOD is reset to 1.0mm if it is no valid Glass Mode value
+    pub fn set_glass_mode(&mut self, on: bool) -> Result<()> {
+        self.set_bytes(|fields| &fields.glass_mode, &[on.into()])?;
+        self.coerce_glass_mode_lod();
+        Ok(())
+    }
+
+    pub fn angle_snapping(&self) -> Result<bool> {
+        self.switch(|fields| &fields.angle_snapping)
+    }
+
+    pub fn set_angle_snapping(&mut self, on: bool) -> Result<()> {
+        self.set_bytes(|fields| &fields.angle_snapping, &[on.into()])
+    }
+
+    pub fn ripple_control(&self) -> Result<bool> {
+        self.switch(|fields| &fields.ripple_control)
+    }
+
+    pub fn set_ripple_control(&mut self, on: bool) -> Result<()> {
+        self.set_bytes(|fields| &fields.ripple_control, &[on.into()])
+    }
+
+    pub fn motion_sync(&self) -> Result<bool> {
+        self.switch(|fields| &fields.motion_sync)
+    }
+
+    pub fn set_motion_sync(&mut self, on: bool) -> Result<()> {
+        self.set_bytes(|fields| &fields.motion_sync, &[on.into()])
+    }
+
+    /// number of CPI stages available when cycling, 1 to 4
+    pub fn cpi_levels(&self) -> Result<u8> {
+        let code = self.byte(|fields| &fields.cpi_levels)?;
+        match code {
+            1..=4 => Ok(code),
+            _ => Err(self.invalid(|fields| &fields.cpi_levels, code)),
+        }
+    }
+
+    /// sets the number of CPI stages available when cycling, 1 to 4
+    pub fn set_cpi_levels(&mut self, levels: u8) -> Result<()> {
+        if !(1..=4).contains(&levels) {
+            return Err(Error::InvalidValue {
+                field: self.profile_fields.cpi_levels.name.clone(),
+                message: format!("{levels} is out of range, expected 1 to 4"),
+            });
+        }
+        self.set_bytes(|fields| &fields.cpi_levels, &[levels])
+    }
+
+    /// resolution of CPI stage `n`, 1 to 4
+    pub fn cpi_stage(&self, n: usize) -> Result<CpiStage> {
+        let attribute = Self::cpi_attribute(n)?;
+        let data = self.bytes(attribute)?;
+        CpiStage::from_bytes(&data).map_err(|message| {
+            Error::InvalidProfile(format!(
+                "{}: {message}",
+                attribute(&self.profile_fields).name
+            ))
+        })
+    }
+
+    /// sets the resolution of CPI stage `n`, 1 to 4
+    pub fn set_cpi_stage(&mut self, n: usize, stage: CpiStage) -> Result<()> {
+        let attribute = Self::cpi_attribute(n)?;
+        self.set_bytes(attribute, &stage.to_bytes())
+    }
+
+    pub fn button_filter(&self, button: Button) -> Result<ClickFilter> {
+        let code = self.byte(|fields| button.filter_attribute(fields))?;
+        ClickFilter::from_code(code)
+            .filter(|filter| button.supports_gx_modes() || matches!(filter, ClickFilter::Level(_)))
+            .ok_or_else(|| self.invalid(|fields| button.filter_attribute(fields), code))
+    }
+
+    /// sets the multiclick filter of `button`, fails with `Error::InvalidValue`
+    /// for GX modes on buttons not supporting them
+    pub fn set_button_filter(&mut self, button: Button, filter: ClickFilter) -> Result<()> {
+        if !button.supports_gx_modes() && !matches!(filter, ClickFilter::Level(_)) {
+            return Err(Error::InvalidValue {
+                field: button.filter_attribute(&self.profile_fields).name.clone(),
+                message: format!("{filter:?} is only supported by the left and right button"),
+            });
+        }
+        let code = filter.code();
+        self.set_bytes(|fields| button.filter_attribute(fields), &[code])
+    }
+
+    /// action bound to `button`
+    pub fn button_action(&self, button: ProgrammableButton) -> Result<ButtonAction> {
+        let data = self.bytes(|fields| button.attribute(fields))?;
+        Ok(ButtonAction::decode(&data))
+    }
+
+    /// Binds `action` to `button`, fails with `Error::InvalidValue` for
+    /// `ButtonAction::FixedCpi` on the scroll wheel.
+    pub fn set_button_action(
+        &mut self,
+        button: ProgrammableButton,
+        action: ButtonAction,
+    ) -> Result<()> {
+        if matches!(action, ButtonAction::FixedCpi(_)) && !button.supports_fixed_cpi() {
+            return Err(Error::InvalidValue {
+                field: button.attribute(&self.profile_fields).name.clone(),
+                message: "Fixed CPI is not supported by the scroll wheel".into(),
+            });
+        }
+        self.set_bytes(|fields| button.attribute(fields), &action.encode())
+    }
+
+    /// attribute of CPI stage `n`
+    fn cpi_attribute(n: usize) -> Result<fn(&MouseProfile) -> &ProfileAttribute> {
+        match n {
+            1 => Ok(|fields| &fields.cpi_prof1),
+            2 => Ok(|fields| &fields.cpi_prof2),
+            3 => Ok(|fields| &fields.cpi_prof3),
+            4 => Ok(|fields| &fields.cpi_prof4),
+            _ => Err(Error::InvalidValue {
+                field: "CPI stage".into(),
+                message: format!("stage {n} does not exist, expected 1 to 4"),
+            }),
+        }
+    }
+
+    /// bytes of the attribute selected by `attribute`
+    fn bytes(&self, attribute: impl Fn(&MouseProfile) -> &ProfileAttribute) -> Result<Vec<u8>> {
+        let attribute = attribute(&self.profile_fields);
+        attribute.check_firmware(self.firmware)?;
+        Ok(attribute
+            .all_addresses()
+            .map(|address| self.profile_buf[address])
+            .collect())
+    }
+
+    /// first byte of the attribute selected by `attribute`
+    fn byte(&self, attribute: impl Fn(&MouseProfile) -> &ProfileAttribute) -> Result<u8> {
+        Ok(self.bytes(attribute)?[0])
+    }
+
+    /// value of the switch selected by `attribute`
+    fn switch(&self, attribute: impl Fn(&MouseProfile) -> &ProfileAttribute) -> Result<bool> {
+        match self.byte(&attribute)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            code => Err(self.invalid(attribute, code)),
+        }
+    }
+
+    /// stores `data` at the addresses of the attribute selected by `attribute`
+    fn set_bytes(
+        &mut self,
+        attribute: impl Fn(&MouseProfile) -> &ProfileAttribute,
+        data: &[u8],
+    ) -> Result<()> {
+        let attribute = attribute(&self.profile_fields);
+        attribute.check_firmware(self.firmware)?;
+        let addresses: Vec<usize> = attribute.all_addresses().collect();
+        for (address, value) in addresses.into_iter().zip(data) {
+            self.profile_buf[address] = *value;
+        }
+        Ok(())
+    }
+
+    /// error for the invalid stored `code` of the attribute selected by `attribute`
+    fn invalid(&self, attribute: impl Fn(&MouseProfile) -> &ProfileAttribute, code: u8) -> Error {
+        Error::InvalidProfile(format!(
+            "{}: invalid value {code:#04x}",
+            attribute(&self.profile_fields).name
+        ))
+    }
+}
@@ -0,0 +1,38 @@
+#[cfg(target_os = "linux")]
+pub mod hidraw;
+pub mod recording;
+pub mod simulated;
+pub mod usb;
+
+use std::time::Duration;
+
+use crate::error::Result;
+
+/// Transport used by the `Handler` to exchange feature reports with the mouse.
+/// Implementations only move bytes, the handshake and profile logic lives in
+/// the `Handler`, which allows plugging in alternative backends and test
+/// doubles.
+pub trait Transport {
+    /// Sends `data` as SET_REPORT feature request and returns the number of
+    /// bytes sent.
+    ///
+    /// * `data`: complete report, starting with the report signature
+    /// * `timeout`: maximum duration of the transfer
+    fn send_feature_report(&self, data: &[u8], timeout: Duration) -> Result<usize>;
+
+    /// Requests a GET_REPORT feature report into `buf` and returns the number
+    /// of bytes read.
+    ///
+    /// * `buf`: buffer to read into, its length is the requested length
+    /// * `timeout`: maximum duration of the transfer
+    fn get_feature_report(&self, buf: &mut [u8], timeout: Duration) -> Result<usize>;
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send_feature_report(&self, data: &[u8], timeout: Duration) -> Result<usize> {
+        (**self).send_feature_report(data, timeout)
+    }
+    fn get_feature_report(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        (**self).get_feature_report(buf, timeout)
+    }
+}
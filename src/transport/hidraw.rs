@@ -0,0 +1,187 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::lock::DeviceLock;
+use crate::transport::{Transport, usb::UsbTransport};
+
+/// ioctl request numbers of linux/hidraw.h
+/// both are defined as _IOC(_IOC_WRITE|_IOC_READ, 'H', nr, len)
+struct HidrawIoctl;
+impl HidrawIoctl {
+    const DIR_READ_WRITE: libc::c_ulong = 3;
+    const TYPE: libc::c_ulong = b'H' as libc::c_ulong;
+    const SET_FEATURE: libc::c_ulong = 0x06;
+    const GET_FEATURE: libc::c_ulong = 0x07;
+
+    fn request(nr: libc::c_ulong, len: usize) -> libc::c_ulong {
+        (Self::DIR_READ_WRITE << 30) | ((len as libc::c_ulong) << 16) | (Self::TYPE << 8) | nr
+    }
+}
+
+/// Linux hidraw transport talking to the mouse through /dev/hidrawN
+/// - init: locating the hidraw node of the mouse and opening it
+/// - open: opening a known hidraw node, optionally waiting for the device lock
+///
+/// Feature reports are exchanged with the HIDIOCSFEATURE/HIDIOCGFEATURE
+/// ioctls, the kernel driver stays attached so the mouse keeps working as a
+/// pointer. The kernel sends the first byte of every report both as report
+/// ID (wValue) and as first data byte. The sniffed protocol uses report ID
+/// 0xa1 for all requests but starts profile writes with 0xa0, which hidraw
+/// cannot express, so profile writes fail with `Error::Protocol` until the
+/// hidraw behaviour of the mouse is verified on hardware.
+/// The ioctls block until the kernel's own control transfer timeout, the
+/// timeout passed by the `Handler` is not applied.
+/// The node is opened under the same `DeviceLock` as the usb backend, keyed
+/// by the port path of the usb device the node belongs to.
+///
+/// * `file`: opened hidraw node
+/// * `path`: path of the hidraw node
+/// * `_lock`: inter-process lock of the device
+pub struct HidrawTransport {
+    file: File,
+    path: PathBuf,
+    _lock: DeviceLock,
+}
+impl HidrawTransport {
+    const SYSFS_HIDRAW: &str = "/sys/class/hidraw";
+    const REPORT_ID: u8 = 0xa1;
+
+    pub fn init(lock_wait: Option<Duration>) -> Result<Self> {
+        Self::open(&Self::find()?, lock_wait)
+    }
+
+    /// Opens the hidraw node at `path`. Fails with `Error::DeviceBusy` if
+    /// another process holds the device lock, or waits up to `lock_wait` for it.
+    pub fn open(path: &Path, lock_wait: Option<Duration>) -> Result<Self> {
+        let lock = DeviceLock::acquire(&Self::port_path(path), lock_wait)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(device_error)?;
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            _lock: lock,
+        })
+    }
+
+    /// Port path of the usb device the hidraw node at `path` belongs to, e.g.
+    /// `1-2.3`, taken from its usb interface `1-2.3:1.1` in sysfs. Nodes
+    /// without usb interface are keyed by their name.
+    fn port_path(path: &Path) -> String {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        fs::canonicalize(Path::new(Self::SYSFS_HIDRAW).join(&*name).join("device/.."))
+            .ok()
+            .and_then(|interface| {
+                let interface = interface.file_name()?.to_str()?.to_string();
+                interface.split_once(':').map(|(port, _)| port.to_string())
+            })
+            .unwrap_or_else(|| name.into_owned())
+    }
+
+    /// returns the path of the opened hidraw node
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Searches sysfs for the hidraw node belonging to the configuration
+    /// interface of the mouse, matching VID, PID and interface number.
+    pub fn find() -> Result<PathBuf> {
+        let entries = fs::read_dir(Self::SYSFS_HIDRAW).map_err(device_error)?;
+        let mut nodes: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        nodes.sort();
+
+        nodes
+            .into_iter()
+            .find(|node| Self::matches(node))
+            .and_then(|node| node.file_name().map(|name| Path::new("/dev").join(name)))
+            .ok_or(Error::DeviceNotFound)
+    }
+
+    /// checks whether the sysfs hidraw entry `node` belongs to the mouse
+    fn matches(node: &Path) -> bool {
+        // HID_ID=<bus>:<vendor>:<product> in the uevent of the hid device
+        let hid_id = format!(
+            "HID_ID=0003:{:08X}:{:08X}",
+            UsbTransport::VID,
+            UsbTransport::PID
+        );
+        let device_matches = fs::read_to_string(node.join("device/uevent"))
+            .map(|uevent| {
+                uevent
+                    .lines()
+                    .any(|line| line.eq_ignore_ascii_case(&hid_id))
+            })
+            .unwrap_or(false);
+
+        // the parent of the hid device is the usb interface
+        let interface_matches = fs::read_to_string(node.join("device/../bInterfaceNumber"))
+            .ok()
+            .and_then(|number| u8::from_str_radix(number.trim(), 16).ok())
+            .is_some_and(|number| number == UsbTransport::INTERFACE);
+
+        device_matches && interface_matches
+    }
+}
+impl Transport for HidrawTransport {
+    fn send_feature_report(&self, data: &[u8], _timeout: Duration) -> Result<usize> {
+        if data.first() != Some(&Self::REPORT_ID) {
+            return Err(Error::Protocol(format!(
+                "hidraw would send report ID {:#04x} instead of {:#04x}, \
+                 profile writes are only supported by the usb backend",
+                data.first().copied().unwrap_or_default(),
+                Self::REPORT_ID
+            )));
+        }
+        let mut buf = data.to_vec();
+        // SAFETY: `buf` is valid for `buf.len()` bytes which is encoded in the request
+        let res = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                HidrawIoctl::request(HidrawIoctl::SET_FEATURE, buf.len()),
+                buf.as_mut_ptr(),
+            )
+        };
+        if res < 0 {
+            return Err(device_error(io::Error::last_os_error()));
+        }
+        Ok(res as usize)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+        buf[0] = Self::REPORT_ID;
+        // SAFETY: `buf` is valid for `buf.len()` bytes which is encoded in the request
+        let res = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                HidrawIoctl::request(HidrawIoctl::GET_FEATURE, buf.len()),
+                buf.as_mut_ptr(),
+            )
+        };
+        if res < 0 {
+            return Err(device_error(io::Error::last_os_error()));
+        }
+        Ok(res as usize)
+    }
+}
+
+/// maps the errno of a hidraw node to the device errors
+fn device_error(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::NotFound => Error::DeviceNotFound,
+        io::ErrorKind::PermissionDenied => Error::PermissionDenied,
+        io::ErrorKind::TimedOut => Error::Timeout,
+        io::ErrorKind::ResourceBusy => Error::KernelDriverBusy,
+        _ => match e.raw_os_error() {
+            Some(libc::ENODEV) => Error::DeviceNotFound,
+            _ => Error::Io(e),
+        },
+    }
+}
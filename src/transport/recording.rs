@@ -0,0 +1,337 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::transport::Transport;
+use crate::transport::usb::{GetHeader, SetHeader};
+
+/// Direction of a recorded transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// SET_REPORT, host to device
+    Set,
+    /// GET_REPORT, device to host
+    Get,
+}
+
+/// Outcome of a recorded transfer
+/// errors are stored as a short code and their message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Ok(usize),
+    Err { code: String, message: String },
+}
+
+/// Single transfer of a recorded session, stored as one tab separated line:
+/// `<timestamp> <SET|GET> <setup packet> <data> <ok N|err CODE MESSAGE>`
+///
+/// * `timestamp`: time of the transfer since the unix epoch
+/// * `direction`: SET_REPORT or GET_REPORT
+/// * `setup`: setup packet of the control transfer as sent by the usb backend,
+///   transfers of other backends (hidraw ioctls) are logged with the same
+///   packet although they are not framed like that on the wire
+/// * `data`: sent payload for SET_REPORT, received response for GET_REPORT
+/// * `outcome`: transferred bytes or the error of the transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub timestamp: Duration,
+    pub direction: Direction,
+    pub setup: [u8; 8],
+    pub data: Vec<u8>,
+    pub outcome: Outcome,
+}
+impl Record {
+    /// parses a line written by `Display`
+    pub fn parse(line: &str) -> std::result::Result<Self, String> {
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        let [timestamp, direction, setup, data, outcome] = fields[..] else {
+            return Err(format!("expected 5 fields, found {}", fields.len()));
+        };
+        let (secs, micros) = timestamp
+            .split_once('.')
+            .ok_or_else(|| format!("invalid timestamp `{timestamp}`"))?;
+        let timestamp = Duration::from_secs(secs.parse().map_err(|_| "invalid timestamp")?)
+            + Duration::from_micros(micros.parse().map_err(|_| "invalid timestamp")?);
+        let direction = match direction {
+            "SET" => Direction::Set,
+            "GET" => Direction::Get,
+            _ => return Err(format!("invalid direction `{direction}`")),
+        };
+        let setup: [u8; 8] = decode_hex(setup)?
+            .try_into()
+            .map_err(|_| "setup packet needs 8 bytes")?;
+        let outcome = if let Some(len) = outcome.strip_prefix("ok ") {
+            Outcome::Ok(len.parse().map_err(|_| format!("invalid length `{len}`"))?)
+        } else if let Some(err) = outcome.strip_prefix("err ") {
+            let (code, message) = err.split_once(' ').unwrap_or((err, ""));
+            Outcome::Err {
+                code: code.into(),
+                message: message.into(),
+            }
+        } else {
+            return Err(format!("invalid outcome `{outcome}`"));
+        };
+        Ok(Self {
+            timestamp,
+            direction,
+            setup,
+            data: decode_hex(data)?,
+            outcome,
+        })
+    }
+}
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Set => "SET",
+            Direction::Get => "GET",
+        };
+        write!(
+            f,
+            "{}.{:06}\t{direction}\t{}\t{}\t",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros(),
+            encode_hex(&self.setup),
+            encode_hex(&self.data)
+        )?;
+        match &self.outcome {
+            Outcome::Ok(len) => write!(f, "ok {len}"),
+            Outcome::Err { code, message } => write!(f, "err {code} {message}"),
+        }
+    }
+}
+
+/// Line starting every session of a recording file
+const SESSION_MARKER: &str = "# session";
+
+/// Transport wrapper logging every transfer of `inner` as `Record` to a file
+/// - new: wrapping `inner` and creating the recording file
+/// - append: wrapping `inner` and appending a new session to the recording file
+///
+/// Every opened transport starts a session with a `# session` line, so one
+/// file can hold the sessions of a command reopening the device (explore),
+/// which `ReplayTransport::open_session` replays one by one.
+///
+/// * `inner`: transport doing the actual transfers
+/// * `file`: recording file, every record is written immediately
+/// * `path`: path of the recording file
+pub struct RecordingTransport<T: Transport> {
+    inner: T,
+    file: File,
+    path: PathBuf,
+}
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new(inner: T, path: &Path) -> Result<Self> {
+        let mut file = File::create(path).map_err(|e| Error::file(path, e))?;
+        writeln!(file, "# egg_xm28kv2 recording").map_err(|e| Error::file(path, e))?;
+        Self::start_session(inner, file, path)
+    }
+
+    pub fn append(inner: T, path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::file(path, e))?;
+        Self::start_session(inner, file, path)
+    }
+
+    /// writes the session marker to `file`
+    fn start_session(inner: T, mut file: File, path: &Path) -> Result<Self> {
+        writeln!(file, "{SESSION_MARKER}").map_err(|e| Error::file(path, e))?;
+        Ok(Self {
+            inner,
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// returns the wrapped transport
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// appends `record` to the recording file
+    fn log(&self, record: Record) -> Result<()> {
+        writeln!(&self.file, "{record}").map_err(|e| Error::file(&self.path, e))
+    }
+}
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send_feature_report(&self, data: &[u8], timeout: Duration) -> Result<usize> {
+        let timestamp = now();
+        let res = self.inner.send_feature_report(data, timeout);
+        self.log(Record {
+            timestamp,
+            direction: Direction::Set,
+            setup: SetHeader::setup_packet(data.len() as u16),
+            data: data.to_vec(),
+            outcome: outcome(&res),
+        })?;
+        res
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let timestamp = now();
+        let res = self.inner.get_feature_report(buf, timeout);
+        let received = match res {
+            Ok(len) => buf[..len].to_vec(),
+            Err(_) => Vec::new(),
+        };
+        self.log(Record {
+            timestamp,
+            direction: Direction::Get,
+            setup: GetHeader::setup_packet(buf.len() as u16),
+            data: received,
+            outcome: outcome(&res),
+        })?;
+        res
+    }
+}
+
+/// Device serving a session recorded by `RecordingTransport` back to the
+/// library, transfer by transfer. Diverging from the recording (different
+/// direction or payload) fails with `Error::Protocol`.
+/// - open: replaying the first session of a recording file
+/// - open_session: replaying the session with the given index
+///
+/// * `records`: transfers which have not been replayed yet
+pub struct ReplayTransport {
+    records: RefCell<VecDeque<Record>>,
+}
+impl ReplayTransport {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_session(path, 0)
+    }
+
+    /// Opens the `index`-th session of the recording at `path`, recordings
+    /// without session markers are a single session.
+    pub fn open_session(path: &Path, index: usize) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| Error::file(path, e))?;
+        let mut sessions: Vec<VecDeque<Record>> = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            if line.trim() == SESSION_MARKER {
+                sessions.push(VecDeque::new());
+                continue;
+            }
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let record = Record::parse(line)
+                .map_err(|e| Error::Protocol(format!("{}:{}: {e}", path.display(), number + 1)))?;
+            if sessions.is_empty() {
+                sessions.push(VecDeque::new());
+            }
+            sessions.last_mut().unwrap().push_back(record);
+        }
+        let records = sessions
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| Error::Protocol(format!("{} has no session {index}", path.display())))?;
+        Ok(Self {
+            records: RefCell::new(records),
+        })
+    }
+
+    /// number of transfers which have not been replayed yet
+    pub fn remaining(&self) -> usize {
+        self.records.borrow().len()
+    }
+
+    /// takes the next record, which has to be a transfer in `direction`
+    fn next(&self, direction: Direction) -> Result<Record> {
+        let record = self
+            .records
+            .borrow_mut()
+            .pop_front()
+            .ok_or_else(|| Error::Protocol("replay exhausted".into()))?;
+        if record.direction != direction {
+            return Err(Error::Protocol(format!(
+                "replay diverged: expected {:?}, recording has {:?}",
+                direction, record.direction
+            )));
+        }
+        Ok(record)
+    }
+}
+impl Transport for ReplayTransport {
+    fn send_feature_report(&self, data: &[u8], _timeout: Duration) -> Result<usize> {
+        let record = self.next(Direction::Set)?;
+        if record.data != data {
+            return Err(Error::Protocol(format!(
+                "replay diverged: sent {}, recording has {}",
+                encode_hex(data),
+                encode_hex(&record.data)
+            )));
+        }
+        replay_outcome(record.outcome)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+        let record = self.next(Direction::Get)?;
+        let len = record.data.len().min(buf.len());
+        buf[..len].copy_from_slice(&record.data[..len]);
+        replay_outcome(record.outcome).map(|_| len)
+    }
+}
+
+/// time since the unix epoch
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// converts the result of a transfer to its recorded `Outcome`
+fn outcome(res: &Result<usize>) -> Outcome {
+    match res {
+        Ok(len) => Outcome::Ok(*len),
+        Err(e) => {
+            let code = match e {
+                Error::DeviceNotFound => "not_found",
+                Error::PermissionDenied => "permission_denied",
+                Error::KernelDriverBusy => "busy",
+                Error::Timeout => "timeout",
+                _ => "other",
+            };
+            Outcome::Err {
+                code: code.into(),
+                message: e.to_string(),
+            }
+        }
+    }
+}
+
+/// converts a recorded `Outcome` back to the result of a transfer
+fn replay_outcome(outcome: Outcome) -> Result<usize> {
+    match outcome {
+        Outcome::Ok(len) => Ok(len),
+        Outcome::Err { code, message } => Err(match code.as_str() {
+            "not_found" => Error::DeviceNotFound,
+            "permission_denied" => Error::PermissionDenied,
+            "busy" => Error::KernelDriverBusy,
+            "timeout" => Error::Timeout,
+            _ => Error::Protocol(message),
+        }),
+    }
+}
+
+/// lower case hex string of `data` without separators
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// parses a hex string written by `encode_hex`
+fn decode_hex(hex: &str) -> std::result::Result<Vec<u8>, String> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err(format!("invalid hex `{hex}`"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("invalid hex `{hex}`")))
+        .collect()
+}
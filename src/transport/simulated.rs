@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::command::Command;
+use crate::error::{Error, Result};
+use crate::profile::profile_base::Profile;
+use crate::transport::Transport;
+
+/// State of the simulated device
+///
+/// * `pending`: request set by the last SET_REPORT, answered by the next GET_REPORT
+/// * `profile`: stored profile image
+/// * `handshake`: response to the handshake
+struct SimulatedState {
+    pending: Option<Command>,
+    profile: [u8; Profile::PROFILE_SIZE],
+    handshake: [u8; 64],
+}
+
+/// In-memory XM2 8k v2 implementing the protocol of sniffed_protocol.md:
+/// - 0xa1 0x02: handshake, answered with the 64 byte handshake response
+/// - 0xa1 0x12: read profile, answered with the stored 1041 byte profile
+/// - 0xa0 0x11: write profile, the 1041 byte report replaces the stored profile
+///
+/// Allows running the full read/modify/write cycle of the `Handler` without
+/// any USB hardware.
+///
+/// * `state`: pending request and stored profile
+pub struct SimulatedDevice {
+    state: RefCell<SimulatedState>,
+}
+impl SimulatedDevice {
+    pub const HANDSHAKE_RESPONSE: [u8; 64] = [
+        0xa1, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x02, 0x01, 0x00, 0x67, 0x33, 0x80, 0x19, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+    ];
+
+    /// creates a simulated device holding `default_profile`
+    pub fn new() -> Self {
+        Self::with_profile(Self::default_profile())
+    }
+
+    /// creates a simulated device holding `profile`
+    pub fn with_profile(profile: [u8; Profile::PROFILE_SIZE]) -> Self {
+        Self {
+            state: RefCell::new(SimulatedState {
+                pending: None,
+                profile,
+                handshake: Self::HANDSHAKE_RESPONSE,
+            }),
+        }
+    }
+
+    /// returns a copy of the currently stored profile image
+    pub fn profile(&self) -> [u8; Profile::PROFILE_SIZE] {
+        self.state.borrow().profile
+    }
+
+    /// replaces the stored profile image, as if changed on the device itself
+    pub fn set_profile(&self, profile: [u8; Profile::PROFILE_SIZE]) {
+        self.state.borrow_mut().profile = profile;
+    }
+
+    /// replaces the handshake response, e.g. to simulate another firmware or device
+    pub fn set_handshake_response(&self, response: [u8; 64]) {
+        self.state.borrow_mut().handshake = response;
+    }
+
+    /// Profile image with the `Profile::DEFAULTS` for every mapped address,
+    /// unmapped bytes are zero.
+    pub fn default_profile() -> [u8; Profile::PROFILE_SIZE] {
+        let mut profile = Profile::init();
+        profile.profile_buf = [0; Profile::PROFILE_SIZE];
+        profile.profile_buf[..2].copy_from_slice(&Command::ReadProfile.signature());
+        profile.reset();
+        profile.profile_buf
+    }
+}
+impl Default for SimulatedDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Transport for SimulatedDevice {
+    fn send_feature_report(&self, data: &[u8], _timeout: Duration) -> Result<usize> {
+        let mut state = self.state.borrow_mut();
+        match Command::from_request(data) {
+            Some(command @ (Command::Handshake | Command::ReadProfile)) => {
+                state.pending = Some(command)
+            }
+            Some(Command::WriteProfile) => {
+                if data.len() != Profile::PROFILE_SIZE {
+                    return Err(Error::Protocol(format!(
+                        "profile write has {} bytes, expected {}",
+                        data.len(),
+                        Profile::PROFILE_SIZE
+                    )));
+                }
+                state.profile.copy_from_slice(data);
+                state.pending = None;
+            }
+            None => {
+                return Err(Error::Protocol(format!(
+                    "unsupported request {:02X?}",
+                    &data[..data.len().min(2)]
+                )));
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+        let mut state = self.state.borrow_mut();
+        let response: &[u8] = match state.pending.take() {
+            Some(Command::Handshake) => &state.handshake,
+            Some(Command::ReadProfile) => &state.profile,
+            Some(Command::WriteProfile) | None => {
+                return Err(Error::Protocol("no request pending".into()));
+            }
+        };
+        let len = response.len().min(buf.len());
+        buf[..len].copy_from_slice(&response[..len]);
+        Ok(len)
+    }
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rusb::{self, Context, Device, DeviceHandle, UsbContext};
+
+use crate::cleanup;
+use crate::error::{Error, Result};
+use crate::lock::DeviceLock;
+use crate::transport::Transport;
+
+/// Header for SET_REPORT requests:
+/// predefines all the headerfields for the usb interface call
+/// over the handler
+pub(crate) struct SetHeader;
+impl SetHeader {
+    pub(crate) const BMREQUESTTYPE: u8 = 0x21;
+    pub(crate) const BREQUEST: u8 = 0x09;
+    pub(crate) const WVALUE: u16 = 0x03a1;
+    pub(crate) const WINDEX: u16 = 0x0001;
+
+    /// setup packet of a request with `length` bytes of data
+    pub(crate) fn setup_packet(length: u16) -> [u8; 8] {
+        setup_packet(
+            Self::BMREQUESTTYPE,
+            Self::BREQUEST,
+            Self::WVALUE,
+            Self::WINDEX,
+            length,
+        )
+    }
+}
+
+/// Header for GET_REPORT requests:
+/// predefines all the headerfields for the usb interface vall
+/// over the handler
+pub(crate) struct GetHeader;
+impl GetHeader {
+    pub(crate) const BMREQUESTTYPE: u8 = 0xa1;
+    pub(crate) const BREQUEST: u8 = 0x01;
+    pub(crate) const WVALUE: u16 = 0x03a1;
+    pub(crate) const WINDEX: u16 = 0x0001;
+
+    /// setup packet of a request with `length` bytes of data
+    pub(crate) fn setup_packet(length: u16) -> [u8; 8] {
+        setup_packet(
+            Self::BMREQUESTTYPE,
+            Self::BREQUEST,
+            Self::WVALUE,
+            Self::WINDEX,
+            length,
+        )
+    }
+}
+
+/// assembles the 8 byte setup packet of a control transfer
+fn setup_packet(request_type: u8, request: u8, value: u16, index: u16, length: u16) -> [u8; 8] {
+    let [value_low, value_high] = value.to_le_bytes();
+    let [index_low, index_high] = index.to_le_bytes();
+    let [length_low, length_high] = length.to_le_bytes();
+    [
+        request_type,
+        request,
+        value_low,
+        value_high,
+        index_low,
+        index_high,
+        length_low,
+        length_high,
+    ]
+}
+
+/// Connected XM2 8k v2 as found by `UsbTransport::list`
+///
+/// * `bus`: usb bus number
+/// * `address`: device address on the bus
+/// * `port_path`: physical port path, e.g. `1-2.3`
+/// * `serial`: serial number string, `None` if it could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceEntry {
+    pub bus: u8,
+    pub address: u8,
+    pub port_path: String,
+    pub serial: Option<String>,
+}
+impl fmt::Display for DeviceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bus {:03} address {:03} port {} serial {}",
+            self.bus,
+            self.address,
+            self.port_path,
+            self.serial.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+/// Selects one of several connected mice
+/// parsed from the following forms:
+/// - `BUS:ADDRESS`, e.g. `1:7` or `001:007`
+/// - `BUS-PORT[.PORT...]`, e.g. `1-2.3`
+/// - `serial:SERIAL`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSelector {
+    BusAddress { bus: u8, address: u8 },
+    PortPath(String),
+    Serial(String),
+}
+impl DeviceSelector {
+    /// checks whether `entry` is selected
+    pub fn matches(&self, entry: &DeviceEntry) -> bool {
+        match self {
+            Self::BusAddress { bus, address } => entry.bus == *bus && entry.address == *address,
+            Self::PortPath(path) => entry.port_path == *path,
+            Self::Serial(serial) => entry.serial.as_ref() == Some(serial),
+        }
+    }
+}
+impl FromStr for DeviceSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Some(serial) = s.strip_prefix("serial:") {
+            return Ok(Self::Serial(serial.into()));
+        }
+        if let Some((bus, address)) = s.split_once(':') {
+            return match (bus.parse(), address.parse()) {
+                (Ok(bus), Ok(address)) => Ok(Self::BusAddress { bus, address }),
+                _ => Err(format!("invalid BUS:ADDRESS selector `{s}`")),
+            };
+        }
+        let valid_path = s.split_once('-').is_some_and(|(bus, ports)| {
+            bus.parse::<u8>().is_ok() && ports.split('.').all(|port| port.parse::<u8>().is_ok())
+        });
+        if valid_path {
+            return Ok(Self::PortPath(s.into()));
+        }
+        Err(format!(
+            "invalid device selector `{s}`, expected BUS:ADDRESS, BUS-PORT[.PORT] or serial:SERIAL"
+        ))
+    }
+}
+
+/// libusb transport talking to the mouse with control transfers
+/// - init: opening the first connected device, detaching the kernel driver and claiming the interface
+/// - open: same as init for the device chosen by a `DeviceSelector`, optionally waiting for the device lock
+/// - list: listing all connected devices
+/// - recover: reattaching the kernel driver left detached by a crashed run
+///
+/// The `DeviceLock` of the device is held while the transport exists, so
+/// other instances of the tool can not interleave their requests.
+/// releases the interface and reattaches the kernel driver on destruction of
+/// the object, or on panics and signals once `cleanup::install` was called
+///
+/// * `handle`: handle to the usb device, shared with the cleanup registry
+/// * `claim`: id of the claimed interface in the cleanup registry
+/// * `_lock`: inter-process lock of the device, released after the interface
+pub struct UsbTransport {
+    handle: Arc<DeviceHandle<Context>>,
+    claim: u64,
+    _lock: DeviceLock,
+}
+impl UsbTransport {
+    pub const VID: u16 = 0x3367;
+    pub const PID: u16 = 0x1980;
+    pub const INTERFACE: u8 = 0x01;
+
+    pub fn init() -> Result<Self> {
+        Self::open(None, None)
+    }
+
+    /// Lists all connected mice. The serial number requires opening the
+    /// device, it is `None` if that is not permitted.
+    pub fn list() -> Result<Vec<DeviceEntry>> {
+        Ok(Self::devices()?.iter().map(Self::entry).collect())
+    }
+
+    /// Reattaches the kernel driver of the device chosen by `selector` if it
+    /// was left detached, e.g. by a crashed run. Returns whether the driver
+    /// had to be reattached.
+    pub fn recover(selector: Option<&DeviceSelector>) -> Result<bool> {
+        let device = Self::find(selector)?;
+        // a running instance detached the driver on purpose
+        let _lock = DeviceLock::acquire(&Self::port_path(&device), None)?;
+        let device_handle = device.open()?;
+        if device_handle.kernel_driver_active(Self::INTERFACE)? {
+            return Ok(false);
+        }
+        device_handle
+            .attach_kernel_driver(Self::INTERFACE)
+            .map_err(|e| match e {
+                rusb::Error::Busy => Error::KernelDriverBusy,
+                e => e.into(),
+            })?;
+        Ok(true)
+    }
+
+    /// Opens the device chosen by `selector`, the first connected one if `None`.
+    /// Fails with `Error::DeviceBusy` if another process holds the device
+    /// lock, or waits up to `lock_wait` for it.
+    pub fn open(selector: Option<&DeviceSelector>, lock_wait: Option<Duration>) -> Result<Self> {
+        // searching the device, opening it separately to tell missing
+        // permissions apart from a missing device
+        let device = Self::find(selector)?;
+
+        // locking the device before touching the kernel driver
+        let lock = DeviceLock::acquire(&Self::port_path(&device), lock_wait)?;
+
+        // creating a device handle for usb device
+        let device_handle: DeviceHandle<Context> = device.open()?;
+
+        // if kernel driver is active detach it
+        let detached = device_handle.kernel_driver_active(Self::INTERFACE)?;
+        if detached {
+            device_handle
+                .detach_kernel_driver(Self::INTERFACE)
+                .map_err(|e| match e {
+                    rusb::Error::Busy | rusb::Error::Access => Error::KernelDriverBusy,
+                    e => e.into(),
+                })?;
+        }
+
+        // claim interface, handing the interface back to the kernel on failure
+        if let Err(e) = device_handle.claim_interface(Self::INTERFACE) {
+            if detached {
+                let _ = device_handle.attach_kernel_driver(Self::INTERFACE);
+            }
+            return Err(match e {
+                rusb::Error::Busy | rusb::Error::Access => Error::KernelDriverBusy,
+                e => e.into(),
+            });
+        }
+        let handle = Arc::new(device_handle);
+        let claim = cleanup::register(Arc::clone(&handle), Self::INTERFACE, detached);
+        Ok(Self {
+            handle,
+            claim,
+            _lock: lock,
+        })
+    }
+
+    /// returns the device chosen by `selector`, the first connected one if `None`
+    fn find(selector: Option<&DeviceSelector>) -> Result<Device<Context>> {
+        Self::devices()?
+            .into_iter()
+            .find(|device| selector.is_none_or(|selector| selector.matches(&Self::entry(device))))
+            .ok_or(Error::DeviceNotFound)
+    }
+
+    /// returns all connected devices matching VID and PID
+    fn devices() -> Result<Vec<Device<Context>>> {
+        //  creating USB device context
+        let context: Context = Context::new()?;
+        Ok(context
+            .devices()?
+            .iter()
+            .filter(|device| {
+                device.device_descriptor().is_ok_and(|desc| {
+                    desc.vendor_id() == Self::VID && desc.product_id() == Self::PID
+                })
+            })
+            .collect())
+    }
+
+    /// physical port path of `device`, e.g. `1-2.3`
+    fn port_path(device: &Device<Context>) -> String {
+        let ports: Vec<String> = device
+            .port_numbers()
+            .unwrap_or_default()
+            .iter()
+            .map(|port| port.to_string())
+            .collect();
+        format!("{}-{}", device.bus_number(), ports.join("."))
+    }
+
+    /// describes `device` as `DeviceEntry`
+    pub(crate) fn entry(device: &Device<Context>) -> DeviceEntry {
+        let serial = device.device_descriptor().ok().and_then(|desc| {
+            device
+                .open()
+                .ok()?
+                .read_serial_number_string_ascii(&desc)
+                .ok()
+        });
+        DeviceEntry {
+            bus: device.bus_number(),
+            address: device.address(),
+            port_path: Self::port_path(device),
+            serial,
+        }
+    }
+}
+impl Transport for UsbTransport {
+    fn send_feature_report(&self, data: &[u8], timeout: Duration) -> Result<usize> {
+        use SetHeader as SH;
+        self.handle
+            .write_control(
+                SH::BMREQUESTTYPE,
+                SH::BREQUEST,
+                SH::WVALUE,
+                SH::WINDEX,
+                data,
+                timeout,
+            )
+            .map_err(Error::from)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        use GetHeader as GH;
+        self.handle
+            .read_control(
+                GH::BMREQUESTTYPE,
+                GH::BREQUEST,
+                GH::WVALUE,
+                GH::WINDEX,
+                buf,
+                timeout,
+            )
+            .map_err(Error::from)
+    }
+}
+
+/// destructor closing down open connections on free
+/// errors are reported on stderr, a device which is already gone is ignored
+impl Drop for UsbTransport {
+    fn drop(&mut self) {
+        cleanup::release(self.claim);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selectors_are_parsed() {
+        let cases = [
+            ("1:7", DeviceSelector::BusAddress { bus: 1, address: 7 }),
+            ("001:007", DeviceSelector::BusAddress { bus: 1, address: 7 }),
+            ("1-2.3", DeviceSelector::PortPath("1-2.3".into())),
+            ("3-1", DeviceSelector::PortPath("3-1".into())),
+            ("serial:ABC", DeviceSelector::Serial("ABC".into())),
+            ("serial:A:B", DeviceSelector::Serial("A:B".into())),
+        ];
+        for (input, selector) in cases {
+            assert_eq!(input.parse(), Ok(selector), "{input}");
+        }
+    }
+
+    #[test]
+    fn invalid_selectors_are_rejected() {
+        for input in ["1-", "1-2..3", "-2", "1:", "1:x", "256:1", "1", "", "usb"] {
+            assert!(input.parse::<DeviceSelector>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn selectors_match_their_entry() {
+        let entry = DeviceEntry {
+            bus: 1,
+            address: 7,
+            port_path: "1-2.3".into(),
+            serial: Some("ABC".into()),
+        };
+        for input in ["1:7", "001:007", "1-2.3", "serial:ABC"] {
+            assert!(
+                input.parse::<DeviceSelector>().unwrap().matches(&entry),
+                "{input}"
+            );
+        }
+        for input in ["1:8", "2:7", "1-2", "1-2.3.4", "serial:abc"] {
+            assert!(
+                !input.parse::<DeviceSelector>().unwrap().matches(&entry),
+                "{input}"
+            );
+        }
+    }
+}
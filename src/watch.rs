@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use rusb::{Context, Device, Hotplug, HotplugBuilder, UsbContext};
+
+use crate::error::{Error, Result};
+use crate::handler::{Handler, TransferPolicy, WriteReport};
+use crate::profile::profile_base::Profile;
+use crate::transport::usb::{DeviceEntry, DeviceSelector, UsbTransport};
+
+/// Event reported by `Watcher::run`
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// a mouse was connected (or was already connected on start)
+    Arrived(DeviceEntry),
+    /// the profile was written to the mouse
+    Applied(DeviceEntry, WriteReport),
+    /// writing the profile to the mouse failed
+    Failed(DeviceEntry, Error),
+    /// a mouse was disconnected
+    Left(DeviceEntry),
+}
+
+/// hotplug notification forwarded from the libusb callback to the event loop
+enum HotplugEvent {
+    Arrived(Device<Context>),
+    Left(Device<Context>),
+}
+
+/// libusb hotplug callback, only forwarding the devices since no transfers
+/// may be done from within the callback
+struct HotplugForwarder {
+    sender: Sender<HotplugEvent>,
+}
+impl Hotplug<Context> for HotplugForwarder {
+    fn device_arrived(&mut self, device: Device<Context>) {
+        let _ = self.sender.send(HotplugEvent::Arrived(device));
+    }
+    fn device_left(&mut self, device: Device<Context>) {
+        let _ = self.sender.send(HotplugEvent::Left(device));
+    }
+}
+
+/// Watches for connected mice and applies a profile to every mouse on arrival
+/// - new: creating a watcher applying `profile` with default settings
+/// - run: watching until an error occurs
+///
+/// * `profile`: profile written to every arriving mouse
+/// * `selector`: restricts the watcher to the selected mouse
+/// * `policy`: transfer policy of the handler writing the profile
+/// * `settle`: pause between arrival and writing, giving the device time to initialize
+/// * `lock_wait`: how long to wait for other instances holding the device lock
+pub struct Watcher {
+    pub profile: Profile,
+    pub selector: Option<DeviceSelector>,
+    pub policy: TransferPolicy,
+    pub settle: Duration,
+    pub lock_wait: Option<Duration>,
+}
+impl Watcher {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            selector: None,
+            policy: TransferPolicy::default(),
+            settle: Duration::from_millis(500),
+            lock_wait: Some(Duration::from_secs(10)),
+        }
+    }
+
+    /// Watches for mice until an error occurs, reporting every event to
+    /// `on_event`. Mice connected on start are treated as arriving.
+    pub fn run(&self, mut on_event: impl FnMut(WatchEvent)) -> Result<()> {
+        if !rusb::has_hotplug() {
+            return Err(Error::Usb(rusb::Error::NotSupported));
+        }
+        let context = Context::new()?;
+        let (sender, receiver): (Sender<HotplugEvent>, Receiver<HotplugEvent>) = mpsc::channel();
+        let _registration = HotplugBuilder::new()
+            .vendor_id(UsbTransport::VID)
+            .product_id(UsbTransport::PID)
+            .enumerate(true)
+            .register(&context, Box::new(HotplugForwarder { sender }))?;
+
+        // arrived mice by bus and address, departed ones can not be queried anymore
+        let mut connected: HashMap<(u8, u8), DeviceEntry> = HashMap::new();
+        loop {
+            context.handle_events(None)?;
+            while let Ok(event) = receiver.try_recv() {
+                match event {
+                    HotplugEvent::Arrived(device) => {
+                        let entry = UsbTransport::entry(&device);
+                        if self.selector.as_ref().is_some_and(|s| !s.matches(&entry)) {
+                            continue;
+                        }
+                        connected.insert((entry.bus, entry.address), entry.clone());
+                        on_event(WatchEvent::Arrived(entry.clone()));
+                        thread::sleep(self.settle);
+                        match self.apply(&entry) {
+                            Ok(report) => on_event(WatchEvent::Applied(entry, report)),
+                            Err(e) => on_event(WatchEvent::Failed(entry, e)),
+                        }
+                    }
+                    HotplugEvent::Left(device) => {
+                        if let Some(entry) =
+                            connected.remove(&(device.bus_number(), device.address()))
+                        {
+                            on_event(WatchEvent::Left(entry));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// writes the profile to the mouse described by `entry`
+    fn apply(&self, entry: &DeviceEntry) -> Result<WriteReport> {
+        let selector = DeviceSelector::BusAddress {
+            bus: entry.bus,
+            address: entry.address,
+        };
+        let mut handler = Handler::new(UsbTransport::open(Some(&selector), self.lock_wait)?);
+        handler.set_policy(self.policy);
+
+        let mut profile = Profile::init();
+        profile.update(&mut self.profile.profile_buf.clone());
+        handler.write_profile(&mut profile)
+    }
+}
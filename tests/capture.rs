@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use egg_xm28kv2::capture::{self, CaptureDirection};
+use egg_xm28kv2::error::Error;
+use egg_xm28kv2::profile::profile_base::Profile;
+use egg_xm28kv2::profile::profile_fields::ProfileFieldName;
+
+/// profile images of the fixtures, the signature followed by zeros except
+/// for the LOD byte
+fn image(signature: [u8; 2], lod: u8) -> [u8; Profile::PROFILE_SIZE] {
+    let mut image = [0; Profile::PROFILE_SIZE];
+    image[..2].copy_from_slice(&signature);
+    image[25] = lod;
+    image
+}
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+#[test]
+fn import_usbmon_mmapped_pcap() {
+    let profiles = capture::import(&fixture("usbmon_mmapped.pcap")).unwrap();
+    assert_eq!(profiles.len(), 2);
+
+    assert_eq!(profiles[0].direction, CaptureDirection::Write);
+    assert_eq!(profiles[0].image, image([0xa0, 0x11], 0x03));
+    assert_eq!(profiles[0].timestamp, Duration::new(10, 200_000));
+
+    assert_eq!(profiles[1].direction, CaptureDirection::Read);
+    assert_eq!(profiles[1].image, image([0xa1, 0x12], 0x05));
+    let lod = profiles[1]
+        .profile()
+        .read_field(&ProfileFieldName::LiftoffDistance)
+        .unwrap();
+    assert_eq!(lod, "1.2mm");
+}
+
+#[test]
+fn import_usbpcap_pcapng() {
+    let profiles = capture::import(&fixture("usbpcap.pcapng")).unwrap();
+    assert_eq!(profiles.len(), 2);
+
+    assert_eq!(profiles[0].direction, CaptureDirection::Write);
+    assert_eq!(profiles[0].image, image([0xa0, 0x11], 0x03));
+    assert_eq!(profiles[0].timestamp, Duration::new(20, 100_000));
+
+    assert_eq!(profiles[1].direction, CaptureDirection::Read);
+    assert_eq!(profiles[1].image, image([0xa1, 0x12], 0x05));
+}
+
+#[test]
+fn import_truncated_capture_fails() {
+    assert!(matches!(
+        capture::import(&fixture("truncated.pcap")),
+        Err(Error::InvalidCapture(_))
+    ));
+}
+
+#[test]
+fn import_missing_file_is_a_file_error() {
+    let error = capture::import(&fixture("missing.pcap")).unwrap_err();
+    assert!(matches!(error, Error::File { .. }), "{error:?}");
+    assert_eq!(error.hint(), None);
+}
+
+#[test]
+fn import_binary_timestamp_resolution() {
+    // if_tsresol 2^-63 with timestamps of 2^62 ticks
+    let profiles = capture::import(&fixture("usbpcap_tsresol_binary.pcapng")).unwrap();
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0].timestamp, Duration::from_millis(500));
+}
+
+#[test]
+fn import_unsupported_timestamp_resolution_fails() {
+    // if_tsresol 10^-20 does not fit into u64
+    assert!(matches!(
+        capture::import(&fixture("usbpcap_tsresol_overflow.pcapng")),
+        Err(Error::InvalidCapture(_))
+    ));
+}
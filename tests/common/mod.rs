@@ -0,0 +1,6 @@
+use std::path::PathBuf;
+
+/// backup file for `Handler::factory_reset`, unique per test and process
+pub fn backup_path(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("egg_xm28kv2-{test}-{}.bin", std::process::id()))
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use egg_xm28kv2::device_info::FirmwareVersion;
+use egg_xm28kv2::error::Error;
+use egg_xm28kv2::handler::Handler;
+use egg_xm28kv2::profile::profile_fields::{ProfileFieldName, SNIFFED_FIRMWARE};
+use egg_xm28kv2::transport::simulated::SimulatedDevice;
+
+/// simulated mouse reporting firmware 2.0.0, older than `SNIFFED_FIRMWARE`
+fn old_firmware_device() -> SimulatedDevice {
+    let device = SimulatedDevice::new();
+    let mut response = SimulatedDevice::HANDSHAKE_RESPONSE;
+    response[17..20].copy_from_slice(&[2, 0, 0]);
+    device.set_handshake_response(response);
+    device
+}
+
+#[test]
+fn simulated_firmware_supports_all_fields() {
+    let handler = Handler::new(SimulatedDevice::new());
+    assert_eq!(handler.handshake().unwrap().firmware, SNIFFED_FIRMWARE);
+    let mut profile = handler.read_profile().unwrap();
+    profile
+        .set_field(&ProfileFieldName::SensorAngleTuning, "-5")
+        .unwrap();
+    assert!(handler.write_profile(&mut profile).unwrap().is_verified());
+}
+
+#[test]
+fn read_field_fails_for_unsupported_field() {
+    let handler = Handler::new(old_firmware_device());
+    let profile = handler.read_profile().unwrap();
+    assert_eq!(profile.firmware, Some(FirmwareVersion::new(2, 0, 0)));
+    assert!(matches!(
+        profile.read_field(&ProfileFieldName::GlassMode),
+        Err(Error::UnsupportedField { .. })
+    ));
+    assert!(profile.read_field(&ProfileFieldName::PollRate).is_ok());
+}
+
+#[test]
+fn set_field_fails_for_unsupported_field() {
+    let handler = Handler::new(old_firmware_device());
+    let mut profile = handler.read_profile().unwrap();
+    assert!(matches!(
+        profile.set_field(&ProfileFieldName::SensorAngleTuning, "10"),
+        Err(Error::UnsupportedField { .. })
+    ));
+}
+
+#[test]
+fn write_profile_fails_if_unsupported_field_changes() {
+    let handler = Handler::new(old_firmware_device());
+    let mut profile = handler.read_profile().unwrap();
+    // unchanged unsupported fields are fine
+    assert!(handler.write_profile(&mut profile).unwrap().is_verified());
+
+    // sensor angle tuning at 128
+    profile.profile_buf[128] = 0x05;
+    assert!(matches!(
+        handler.write_profile(&mut profile),
+        Err(Error::UnsupportedField { .. })
+    ));
+    assert_eq!(handler.transport().profile()[128], 0x00);
+}
+
+#[test]
+fn factory_reset_keeps_unsupported_fields() {
+    let device = old_firmware_device();
+    let mut image = device.profile();
+    image[128] = 0x05;
+    device.set_profile(image);
+    let handler = Handler::new(device);
+
+    let backup = common::backup_path("factory_reset_keeps_unsupported_fields");
+    let report = handler.factory_reset(&backup).unwrap();
+    std::fs::remove_file(&backup).unwrap();
+    assert!(report.is_verified());
+    assert_eq!(handler.transport().profile()[128], 0x05);
+}
@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use egg_xm28kv2::error::Error;
+use egg_xm28kv2::handler::Handler;
+use egg_xm28kv2::transport::recording::{RecordingTransport, ReplayTransport};
+use egg_xm28kv2::transport::simulated::SimulatedDevice;
+
+/// recording file unique to this test process
+fn recording(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("egg_xm28kv2-{name}-{}.rec", std::process::id()))
+}
+
+#[test]
+fn recorded_read_profile_is_replayed() {
+    let path = recording("round-trip");
+    let recorded = Handler::new(RecordingTransport::new(SimulatedDevice::new(), &path).unwrap())
+        .read_profile()
+        .unwrap();
+
+    let handler = Handler::new(ReplayTransport::open(&path).unwrap());
+    let replayed = handler.read_profile().unwrap();
+    assert_eq!(replayed.profile_buf, recorded.profile_buf);
+    assert_eq!(replayed.firmware, recorded.firmware);
+    assert_eq!(handler.transport().remaining(), 0);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn appended_sessions_are_replayed_in_order() {
+    let path = recording("sessions");
+    let first = Handler::new(RecordingTransport::new(SimulatedDevice::new(), &path).unwrap())
+        .read_profile()
+        .unwrap();
+    // the device is reopened after the profile was changed on it
+    let mut changed = SimulatedDevice::default_profile();
+    changed[25] = 0x05;
+    let device = SimulatedDevice::with_profile(changed);
+    let second = Handler::new(RecordingTransport::append(device, &path).unwrap())
+        .read_profile()
+        .unwrap();
+    assert_ne!(first.profile_buf, second.profile_buf);
+
+    for (index, expected) in [first, second].iter().enumerate() {
+        let handler = Handler::new(ReplayTransport::open_session(&path, index).unwrap());
+        let replayed = handler.read_profile().unwrap();
+        assert_eq!(
+            replayed.profile_buf, expected.profile_buf,
+            "session {index}"
+        );
+        assert_eq!(handler.transport().remaining(), 0);
+    }
+    assert!(matches!(
+        ReplayTransport::open_session(&path, 2),
+        Err(Error::Protocol(_))
+    ));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use egg_xm28kv2::error::Error;
+use egg_xm28kv2::handler::Handler;
+use egg_xm28kv2::profile::button_action::{ButtonAction, ProgrammableButton};
+use egg_xm28kv2::profile::profile_fields::ProfileFieldName;
+use egg_xm28kv2::profile::settings::{Button, ClickFilter, CpiStage};
+use egg_xm28kv2::transport::simulated::SimulatedDevice;
+
+#[test]
+fn read_modify_write_cycle() {
+    let handler = Handler::new(SimulatedDevice::new());
+    let stage: CpiStage = "800x1600".parse().unwrap();
+    let mut profile = handler.read_profile().unwrap();
+    assert_eq!(
+        profile
+            .read_field(&ProfileFieldName::LiftoffDistance)
+            .unwrap(),
+        "0.7mm"
+    );
+
+    profile
+        .set_field(&ProfileFieldName::LiftoffDistance, "1.2mm")
+        .unwrap();
+    profile.set_cpi_stage(2, stage).unwrap();
+    let report = handler.write_profile(&mut profile).unwrap();
+    assert!(report.is_verified());
+
+    let stored = handler.transport().profile();
+    assert_eq!(&stored[..2], &[0xa0, 0x11]);
+    assert_eq!(stored[25], 0x05);
+    assert_eq!(&stored[57..61], &[0x20, 0x03, 0x40, 0x06]);
+
+    let read = handler.read_profile().unwrap();
+    assert_eq!(
+        read.read_field(&ProfileFieldName::LiftoffDistance).unwrap(),
+        "1.2mm"
+    );
+    assert_eq!(read.cpi_stage(2).unwrap(), stage);
+}
+
+#[test]
+fn handshake_with_wrong_vid_pid_fails() {
+    let device = SimulatedDevice::new();
+    let mut response = SimulatedDevice::HANDSHAKE_RESPONSE;
+    // VID 0x1234 PID 0x5678
+    response[20..24].copy_from_slice(&[0x34, 0x12, 0x78, 0x56]);
+    device.set_handshake_response(response);
+    let handler = Handler::new(device);
+
+    assert!(matches!(
+        handler.handshake(),
+        Err(Error::HandshakeMismatch { .. })
+    ));
+    assert!(matches!(
+        handler.read_profile(),
+        Err(Error::HandshakeMismatch { .. })
+    ));
+}
+
+#[test]
+fn button_filters_match_protocol_addresses() {
+    let mut profile = Handler::new(SimulatedDevice::new()).read_profile().unwrap();
+    profile
+        .set_button_filter(Button::Left, ClickFilter::GxSafeMode)
+        .unwrap();
+    profile
+        .set_button_filter(Button::Right, ClickFilter::GxSpeedMode)
+        .unwrap();
+    // LB MF <-> 77, RB MF <-> 84
+    assert_eq!(profile.profile_buf[77], 0xf0);
+    assert_eq!(profile.profile_buf[84], 0xf1);
+}
+
+#[test]
+fn factory_reset_clears_button_data_fields() {
+    let handler = Handler::new(SimulatedDevice::new());
+    let mut profile = handler.read_profile().unwrap();
+    let action: ButtonAction = "fixed-cpi:800".parse().unwrap();
+    profile
+        .set_button_action(ProgrammableButton::Forward, action)
+        .unwrap();
+    handler.write_profile(&mut profile).unwrap();
+
+    let backup = common::backup_path("factory_reset_clears_button_data_fields");
+    let report = handler.factory_reset(&backup).unwrap();
+    std::fs::remove_file(&backup).unwrap();
+    assert!(report.is_verified());
+    assert_eq!(
+        &handler.transport().profile()[92..98],
+        &[0x00, 0x10, 0, 0, 0, 0]
+    );
+}